@@ -1,10 +1,123 @@
+use crate::error::HifzrError;
+use crate::event::{emit, Event, Sink};
 use crate::models::{Verse, ChapterResponse};
 use anyhow::{Result, Context};
 use reqwest::{Client, StatusCode};
 use serde::Serialize;
 use tokio::time::{sleep, Duration};
 
-const BASE: &str = "https://api.quran.com/api/v4";
+/// Where the API and the audio CDN live. Defaults to quran.com's hosts;
+/// `HIFZR_API_BASE` and `HIFZR_CDN_BASE` point them at a mirror.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    pub api: String,
+    pub cdn: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints {
+            api: "https://api.quran.com/api/v4".into(),
+            cdn: "https://audio.qurancdn.com".into(),
+        }
+    }
+}
+
+impl Endpoints {
+    pub fn from_env() -> Self {
+        let var = |k| std::env::var(k).ok().map(|v: String| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty());
+        let d = Endpoints::default();
+        Endpoints {
+            api: var("HIFZR_API_BASE").unwrap_or(d.api),
+            cdn: var("HIFZR_CDN_BASE").unwrap_or(d.cdn),
+        }
+    }
+}
+
+static ENDPOINTS: std::sync::OnceLock<Endpoints> = std::sync::OnceLock::new();
+
+/// Use these hosts instead of the environment's. Only works before the first
+/// request; returns false once the endpoints are already in use.
+pub fn set_endpoints(e: Endpoints) -> bool {
+    ENDPOINTS.set(e).is_ok()
+}
+
+pub fn endpoints() -> &'static Endpoints {
+    ENDPOINTS.get_or_init(Endpoints::from_env)
+}
+
+/// How the HTTP client connects. Without `proxy`, reqwest already honors
+/// `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` (and `NO_PROXY`) from the environment.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// http://, https:// or socks5:// proxy for every request
+    pub proxy: Option<String>,
+    /// Give up on a connection attempt after this long
+    pub connect_timeout: Duration,
+    /// Give up on a request the server sends nothing for this long. Not a cap
+    /// on the whole transfer, so big files on slow links still finish.
+    pub timeout: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig { proxy: None, connect_timeout: Duration::from_secs(10), timeout: Duration::from_secs(30) }
+    }
+}
+
+impl ClientConfig {
+    pub fn build(&self) -> Result<Client> {
+        let mut b = Client::builder()
+            .gzip(true)
+            .brotli(true)
+            .user_agent(concat!("hifzr/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(self.connect_timeout.min(self.timeout))
+            .read_timeout(self.timeout);
+        if let Some(p) = &self.proxy {
+            b = b.proxy(reqwest::Proxy::all(p).with_context(|| format!("bad --proxy {p:?}"))?);
+        }
+        Ok(b.build()?)
+    }
+}
+
+/// How hard to push when the API pushes back
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Give up on the whole run once a 429 outlives our retries
+    pub stop_on_429: bool,
+    /// Debugging: stop paginating verse listings after this many pages
+    pub max_pages: Option<u32>,
+    /// Retries after a 429 or a dropped/timed-out connection
+    pub max_retries: u32,
+    /// First wait between retries; doubles each time (a Retry-After wins)
+    pub base_backoff: Duration,
+    /// Told about each retry and about listings cut short by `max_pages`
+    pub progress: Option<Sink>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig { stop_on_429: false, max_pages: None, max_retries: 5, base_backoff: Duration::from_millis(500), progress: None }
+    }
+}
+
+// Never sleep longer than this for one Retry-After
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The server kept answering 429 after every retry
+#[derive(Debug)]
+pub struct RateLimited {
+    pub url: String,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rate limited by {}, try again later", self.url)
+    }
+}
+
+impl std::error::Error for RateLimited {}
 
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct ChapterQuery {
@@ -15,30 +128,74 @@ pub struct ChapterQuery {
     // NEW: keep responses skinny & predictable
     pub words: Option<bool>,      // set false
     pub fields: Option<String>,   // ask only what you need
+    pub translations: Option<String>, // resource ids, e.g. "131"
 }
 
-async fn get_page(client: &Client, url: &str, pq: &ChapterQuery) -> Result<ChapterResponse> {
+// DNS failures, refused/reset connections and timeouts are usually a flaky
+// network, not a real answer; HTTP status errors are left to the caller
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || (e.is_request() && !e.is_builder())
+}
+
+// Retry-After is either seconds ("120") or an HTTP date; capped, and a
+// date in the past means "now"
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let v = resp.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    let wait = match v.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => httpdate::parse_http_date(v).ok()?
+            .duration_since(std::time::SystemTime::now())
+            .unwrap_or_default(),
+    };
+    Some(wait.min(MAX_RETRY_AFTER))
+}
 
+// Send, retrying connection-level failures and 429s with exponential backoff
+// (or the server's Retry-After). A 429 that outlasts the retries is returned
+// for the caller to judge.
+pub async fn send_retrying(req: reqwest::RequestBuilder, retry: &RetryConfig) -> reqwest::Result<reqwest::Response> {
+    send_noting_429s(req, retry, || {}).await
+}
+
+// send_retrying, calling `on_429` for every 429 on the way, the last one
+// included (downloads with --max-jobs slow down on these)
+pub async fn send_noting_429s(req: reqwest::RequestBuilder, retry: &RetryConfig, on_429: impl Fn()) -> reqwest::Result<reqwest::Response> {
     let mut tries = 0u32;
     loop {
-        let resp = client.get(url).query(pq).send().await
-            .with_context(|| format!("send failed: {url}"))?;
+        let Some(attempt) = req.try_clone() else { return req.send().await };
+        let backoff = retry.base_backoff * (1 << tries.min(16));
+        match attempt.send().await {
+            Err(e) if is_transient(&e) && tries < retry.max_retries => {
+                emit(&retry.progress, Event::Note(format!("network error ({}); retrying in {:.1}s", e, backoff.as_secs_f32())));
+                sleep(backoff).await;
+            }
+            Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => {
+                on_429();
+                if tries >= retry.max_retries { return Ok(resp); }
+                sleep(retry_after(&resp).unwrap_or(backoff)).await;
+            }
+            r => return r,
+        }
+        tries += 1;
+    }
+}
 
-        let status = resp.status();                // <— capture BEFORE any move
+async fn get_page(client: &Client, url: &str, pq: &ChapterQuery, retry: &RetryConfig) -> crate::error::Result<ChapterResponse> {
 
-        if status == StatusCode::TOO_MANY_REQUESTS && tries < 5 {
-            sleep(Duration::from_millis(250 * (1 << tries))).await;
-            tries += 1;
-            continue;
-        }
+    let resp = send_retrying(client.get(url).query(pq), retry).await
+        .with_context(|| format!("send failed: {url}"))?;
 
-        // error_for_status() CONSUMES resp, but we no longer need resp itself
-        let resp = resp.error_for_status()
-            .context(format!("HTTP {status} for {url}"))?;
+    let status = resp.status();                // <— capture BEFORE any move
 
-        return resp.json::<ChapterResponse>().await
-            .context("decode ChapterResponse failed");
+    if status == StatusCode::TOO_MANY_REQUESTS && retry.stop_on_429 {
+        return Err(anyhow::Error::new(RateLimited { url: url.to_string() }).into());
     }
+    if !status.is_success() {
+        return Err(HifzrError::Http { url: url.to_string(), status: status.as_u16() });
+    }
+
+    Ok(resp.json::<ChapterResponse>().await
+        .context("decode ChapterResponse failed")?)
 }
 
 
@@ -46,25 +203,254 @@ pub async fn fetch_chapter(
     client: &Client,
     audio: u32,
     chapter: u32,
-) -> Result<Vec<Verse>> {
+    retry: &RetryConfig,
+) -> crate::error::Result<Vec<Verse>> {
+    fetch_collection(client, audio, &format!("verses/by_chapter/{chapter}"), retry).await
+}
+
+// Any paginated verse listing: an API path ("verses/by_juz/30") or a full URL
+pub async fn fetch_collection(
+    client: &Client,
+    audio: u32,
+    collection: &str,
+    retry: &RetryConfig,
+) -> crate::error::Result<Vec<Verse>> {
+    fetch_collection_translated(client, audio, collection, None, retry).await
+}
+
+/// `fetch_collection`, with each verse's text in `translation` (an id from
+/// `lookup::fetch_translations`) filled into `Verse::translations`
+pub async fn fetch_collection_translated(
+    client: &Client,
+    audio: u32,
+    collection: &str,
+    translation: Option<u32>,
+    retry: &RetryConfig,
+) -> crate::error::Result<Vec<Verse>> {
+    let url = if collection.starts_with("http") {
+        collection.to_string()
+    } else {
+        format!("{}/{}", endpoints().api, collection.trim_start_matches('/'))
+    };
     let mut out = Vec::new();
     let mut page = 1u32;
+    let mut expected: Option<u32> = None;
+    let mut empty_tries = 0u32;
+    let mut fetched = 0u32;
     loop {
-        let url = format!("{BASE}/verses/by_chapter/{chapter}");
+        if let Some(max) = retry.max_pages
+            && fetched >= max {
+            emit(&retry.progress, Event::Warning(format!("stopped after {max} page(s) (--max-pages); {url} is partial ({} verses)", out.len())));
+            return Ok(out);
+        }
         let pq = ChapterQuery {
             audio,
             page: Some(page),
             per_page: Some(50),
             words: Some(false),
             fields: Some("juz_number,hizb_number,verse_key,verse_number,rub_el_hizb_number".into()),
+            translations: translation.map(|t| t.to_string()),
         };
-        let parsed = get_page(client, &url, &pq).await?;
-        if parsed.verses.is_empty() { break; }
+        let parsed = get_page(client, &url, &pq, retry).await?;
+        let next = parsed.pagination.as_ref().and_then(|p| p.next_page);
+        if let Some(t) = parsed.pagination.as_ref().and_then(|p| p.total_records) {
+            expected = Some(t);
+        }
+
+        if parsed.verses.is_empty() {
+            // an empty page while the API still promises more is a hiccup, not the end
+            let more = next.is_some() || expected.is_some_and(|t| (out.len() as u32) < t);
+            if !more { break; }
+            if empty_tries < 2 {
+                empty_tries += 1;
+                sleep(Duration::from_millis(500 * empty_tries as u64)).await;
+                continue;
+            }
+            return Err(anyhow::anyhow!("page {page} of {url} stays empty but {}/{} verses arrived",
+                out.len(), expected.map_or("?".into(), |t| t.to_string())).into());
+        }
+        empty_tries = 0;
+        fetched += 1;
         out.extend(parsed.verses);
-        match parsed.pagination.and_then(|p| p.next_page) {
+        match next {
             Some(next) => page = next,
             None => break,
         }
     }
+    if let Some(t) = expected
+        && (out.len() as u32) < t {
+        return Err(anyhow::anyhow!("{url} returned {} verses but reports {t}", out.len()).into());
+    }
     Ok(out)
 }
+
+// One ayah's tafsir as plain text; `verse_key` is "2:255"
+pub async fn fetch_tafsir(client: &Client, tafsir: u32, verse_key: &str, retry: &RetryConfig) -> Result<String> {
+    #[derive(serde::Deserialize)] struct R { tafsir: T }
+    #[derive(serde::Deserialize)] struct T { #[serde(default)] text: String }
+    let url = format!("{}/tafsirs/{tafsir}/by_ayah/{verse_key}", endpoints().api);
+    let resp = send_retrying(client.get(&url), retry).await
+        .with_context(|| format!("send failed: {url}"))?;
+    let status = resp.status();
+    if status == StatusCode::TOO_MANY_REQUESTS && retry.stop_on_429 {
+        return Err(RateLimited { url }.into());
+    }
+    let r = resp.error_for_status()
+        .context(format!("HTTP {status} for {url}"))?
+        .json::<R>().await
+        .context("decode tafsir failed")?;
+    Ok(strip_html(&r.tafsir.text))
+}
+
+// A translation as plain text: footnote markers (<sup foot_note=..>1</sup>)
+// go along with their number, the rest like a tafsir
+pub(crate) fn translation_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(i) = rest.find("<sup") {
+        out.push_str(&rest[..i]);
+        match rest[i..].find("</sup>") {
+            Some(j) => rest = &rest[i + j + "</sup>".len()..],
+            None => { rest = &rest[i..]; break }
+        }
+    }
+    out.push_str(rest);
+    strip_html(&out)
+}
+
+// Tafsir text comes as HTML: paragraphs become blank lines, tags go,
+// the common entities are decoded
+fn strip_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(i) = rest.find('<') {
+        out.push_str(&rest[..i]);
+        let Some(j) = rest[i..].find('>') else { rest = &rest[i..]; break };
+        let tag = rest[i + 1..i + j].trim_start_matches('/').to_ascii_lowercase();
+        let name = tag.split(|c: char| !c.is_ascii_alphanumeric()).next().unwrap_or("");
+        match name {
+            "p" | "div" | "h1" | "h2" | "h3" | "h4" => out.push_str("\n\n"),
+            "br" | "li" => out.push('\n'),
+            _ => {}
+        }
+        rest = &rest[i + j + 1..];
+    }
+    out.push_str(rest);
+    let out = out.replace("&nbsp;", " ").replace("&lt;", "<").replace("&gt;", ">")
+        .replace("&quot;", "\"").replace("&#39;", "'").replace("&amp;", "&");
+    // collapse the blank-line runs the tag removal leaves behind
+    let mut text = String::new();
+    let mut blank = 0;
+    for line in out.lines().map(str::trim) {
+        if line.is_empty() { blank += 1; continue; }
+        if !text.is_empty() { text.push_str(if blank > 0 { "\n\n" } else { "\n" }); }
+        text.push_str(line);
+        blank = 0;
+    }
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{MockServer, Reply};
+
+    fn fast_retry() -> RetryConfig {
+        RetryConfig { base_backoff: Duration::from_millis(10), ..Default::default() }
+    }
+
+    // one listing page: these verse numbers of surah 1, then `next`
+    fn page(ayahs: &[u32], next: Option<u32>, total: u32) -> Reply {
+        let verses: Vec<String> = ayahs.iter()
+            .map(|a| format!(r#"{{"id": {a}, "verse_number": {a}, "verse_key": "1:{a}", "audio": {{"url": "x/{a}.mp3"}}}}"#))
+            .collect();
+        let next = next.map_or("null".to_string(), |n| n.to_string());
+        Reply::json(&format!(r#"{{"verses": [{}], "pagination": {{"next_page": {next}, "total_records": {total}}}}}"#, verses.join(",")))
+    }
+
+    #[tokio::test]
+    async fn a_transient_empty_page_is_asked_again() {
+        let srv = MockServer::start(vec![
+            page(&[1, 2], Some(2), 3),
+            page(&[], Some(2), 3), // the hiccup
+            page(&[3], None, 3),
+        ]);
+        let verses = fetch_collection(&Client::new(), 1, &format!("{}/verses/by_chapter/1", srv.url), &fast_retry()).await.unwrap();
+        assert_eq!(verses.iter().map(|v| v.verse_number).collect::<Vec<_>>(), vec![1, 2, 3]);
+        let requests = srv.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].contains("page=2") && requests[2].contains("page=2"), "{requests:?}");
+    }
+
+    #[tokio::test]
+    async fn an_empty_page_that_stays_empty_is_an_error() {
+        let srv = MockServer::start(vec![
+            page(&[1, 2], Some(2), 3),
+            page(&[], Some(2), 3),
+            page(&[], Some(2), 3),
+            page(&[], Some(2), 3),
+        ]);
+        let err = fetch_collection(&Client::new(), 1, &format!("{}/verses/by_chapter/1", srv.url), &fast_retry()).await.unwrap_err();
+        assert!(err.to_string().contains("stays empty"), "{err}");
+        assert_eq!(srv.hits(), 4);
+    }
+
+    #[tokio::test]
+    async fn an_empty_last_page_ends_the_listing() {
+        let srv = MockServer::start(vec![page(&[1, 2, 3], Some(2), 3), page(&[], None, 3)]);
+        let verses = fetch_collection(&Client::new(), 1, &format!("{}/v", srv.url), &fast_retry()).await.unwrap();
+        assert_eq!(verses.len(), 3);
+    }
+
+    // the page below, gzipped: {"verses": [1:1, 1:2], "pagination": {"next_page": null, ...}}
+    const GZIPPED_PAGE: &[u8] = b"\
+            \x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x75\x8f\xcb\x0a\xc2\x30\x10\x45\x7f\x25\xcc\xba\x28\x19\
+            \x77\xfd\x15\x91\x10\xcd\x20\xc1\x34\x29\xd3\x44\x2a\x21\xff\xee\xf4\xb1\x91\xe2\x6a\xb8\xe7\x5e\
+            \x0e\x4c\x85\x37\xf1\x44\x13\xf4\xea\x5a\xc1\x3b\xb9\xba\x53\x1b\x34\xb1\x0c\x77\xe2\x1f\xf4\xa2\
+            \x8f\x64\xd0\xbd\x06\x61\xb6\x38\x9f\x24\x57\x28\x1c\x16\x3e\x9f\xf5\x69\x18\x2f\xd0\x5a\xa7\x76\
+            \x1d\x1e\x75\x78\xd4\xe1\x1f\x1d\xee\xba\x9b\xd4\xa3\x7d\xfa\x68\xb3\x4f\x71\xdd\x44\x9a\xb3\x11\
+            \x46\x92\x62\x09\x41\x16\x39\x65\x1b\x0c\xd3\x23\xb1\x5b\x5e\xc2\xd6\xbe\x2e\xbd\xb5\x8c\xe1\x00\
+            \x00\x00";
+
+    #[tokio::test]
+    async fn gzipped_listings_are_decoded() {
+        let srv = MockServer::start(vec![
+            Reply::ok("application/json", GZIPPED_PAGE).header("Content-Encoding", "gzip"),
+        ]);
+        // the client hifzr builds, with decompression on
+        let client = ClientConfig::default().build().unwrap();
+        let verses = fetch_collection(&client, 1, &format!("{}/verses/by_chapter/1", srv.url), &fast_retry()).await.unwrap();
+        assert_eq!(verses.iter().map(|v| v.verse_key.as_str()).collect::<Vec<_>>(), ["1:1", "1:2"]);
+        assert_eq!(verses[1].audio.url, "x/2.mp3");
+    }
+
+    #[tokio::test]
+    async fn dropped_connections_are_retried() {
+        let srv = MockServer::start(vec![Reply::Hangup, Reply::Hangup, Reply::json("{}")]);
+        let resp = send_retrying(Client::new().get(&srv.url), &fast_retry()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(srv.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_run_out() {
+        let srv = MockServer::start(vec![Reply::Hangup, Reply::Hangup, Reply::json("{}")]);
+        let retry = RetryConfig { max_retries: 1, ..fast_retry() };
+        let err = send_retrying(Client::new().get(&srv.url), &retry).await.unwrap_err();
+        assert!(is_transient(&err), "{err}");
+        assert_eq!(srv.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_wait_for_retry_after() {
+        let srv = MockServer::start(vec![Reply::status(429).header("Retry-After", 0), Reply::json("{}")]);
+        let resp = send_retrying(Client::new().get(&srv.url), &fast_retry()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        // out of retries, the 429 itself comes back for the caller to judge
+        let srv = MockServer::start(vec![Reply::status(429), Reply::status(429)]);
+        let retry = RetryConfig { max_retries: 1, ..fast_retry() };
+        let resp = send_retrying(Client::new().get(&srv.url), &retry).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
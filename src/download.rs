@@ -1,15 +1,958 @@
 use anyhow::{Context};
 use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use tokio::{fs, io::AsyncWriteExt};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tokio::{fs, io::AsyncWriteExt, time::Instant};
+
+use crate::api::{RateLimited, RetryConfig};
+use crate::event::{emit, Event, Sink};
+
+/// Knobs for one `run_filter` pass
+#[derive(Debug, Clone, Default)]
+pub struct FilterOpts {
+    pub force: bool,
+    pub only_verses: Option<Vec<u32>>,
+    pub retry: RetryConfig,
+    /// Stop starting new ayahs (and drop the one in flight) past this point
+    pub deadline: Option<Instant>,
+    /// Abort any single file that grows past this many bytes
+    pub max_file_size: Option<u64>,
+    /// One speed cap shared by every download in flight
+    pub max_rate: Option<std::sync::Arc<RateLimiter>>,
+    /// Let the server's 429s move the number of ayahs in flight (`jobs` is
+    /// where it starts)
+    pub adaptive_jobs: Option<std::sync::Arc<AdaptiveJobs>>,
+    /// Check segment timings against each other and the probed file length
+    pub verify_segments: bool,
+    /// Record failed ayahs in retry.json and carry on instead of stopping
+    pub keep_going: bool,
+    /// Also keep a low-bitrate Opus copy of each ayah in this folder
+    pub phone_copy: Option<PathBuf>,
+    /// Transcode each ayah next to its mp3 (needs ffmpeg)
+    pub convert: Option<Convert>,
+    /// With `convert`: remove the mp3 once the converted file is written
+    pub delete_source: bool,
+    /// Loudness-normalize each fresh download to this many LUFS (needs ffmpeg)
+    pub normalize: Option<f64>,
+    /// Keep unfinished downloads as `.part` and continue them with a Range request
+    pub resume_partial: bool,
+    /// Also save this tafsir's text next to each ayah
+    pub tafsir: Option<u32>,
+    /// Also save this translation's text next to each ayah
+    pub translation: Option<u32>,
+    /// Skip ayahs the API has no word segment timings for
+    pub require_segments: bool,
+    /// Ayahs downloaded at once (0 and 1 both mean one at a time)
+    pub jobs: usize,
+    /// ID3 tags for freshly downloaded ayahs (surah downloads only)
+    pub tags: Option<TagInfo>,
+    /// Record size and time of every file actually downloaded (`benchmark`)
+    pub timings: Option<std::sync::Arc<std::sync::Mutex<Vec<FileTiming>>>>,
+    /// Only print each target path and URL; nothing is fetched or written
+    pub dry_run: bool,
+    /// HEAD the ayahs still to fetch and print their total size first
+    pub estimate: bool,
+    /// Report each file's bytes as they arrive (`Event::FileBytes`)
+    pub file_progress: bool,
+    /// Also cut each ayah into per-word clips under words/ (needs ffmpeg)
+    pub words: bool,
+    /// The source's verse listing, already fetched (see `prefetch_chapters`)
+    pub verses: Option<std::sync::Arc<Vec<crate::models::Verse>>>,
+    /// Told how the pass is going: progress, warnings, the dry-run plan
+    pub progress: Option<Sink>,
+}
+
+/// Paces downloads to a total byte rate, however many run at once
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    // since when, and how many bytes went through
+    spent: std::sync::Mutex<(Instant, u64)>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter { bytes_per_sec: bytes_per_sec.max(1), spent: std::sync::Mutex::new((Instant::now(), 0)) }
+    }
+
+    // Count `n` more bytes and sleep while the total is ahead of the rate
+    async fn take(&self, n: u64) {
+        let wait = {
+            let mut spent = self.spent.lock().unwrap();
+            spent.1 += n;
+            let due = std::time::Duration::from_secs_f64(spent.1 as f64 / self.bytes_per_sec as f64);
+            let elapsed = spent.0.elapsed();
+            // idle time (between files, waiting out a retry) doesn't build
+            // up credit for a burst later; restart the count instead
+            if elapsed > due + std::time::Duration::from_secs(1) {
+                *spent = (Instant::now(), 0);
+            }
+            due.saturating_sub(elapsed)
+        };
+        if !wait.is_zero() { tokio::time::sleep(wait).await; }
+    }
+}
+
+/// How many ayahs download at once, tuned like TCP's congestion window: a
+/// 429 halves what was in flight (down to `min`), and every `limit` clean
+/// downloads in a row add one more (up to `max`). Shared by every run_filter
+/// pass, so what one surah learns carries over to the next.
+#[derive(Debug)]
+pub struct AdaptiveJobs {
+    min: usize,
+    max: usize,
+    state: std::sync::Mutex<JobsState>,
+    freed: tokio::sync::Notify,
+}
+
+#[derive(Debug, Default)]
+struct JobsState {
+    /// 0 until the first pass sets its --jobs
+    limit: usize,
+    in_flight: usize,
+    clean: usize,
+}
+
+impl AdaptiveJobs {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        AdaptiveJobs { min, max: max.max(min), state: Default::default(), freed: tokio::sync::Notify::new() }
+    }
+
+    /// Ayahs allowed in flight right now
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    // The first pass's --jobs is the starting point; later ones keep the limit
+    fn start_at(&self, jobs: usize) {
+        let mut s = self.state.lock().unwrap();
+        if s.limit == 0 { s.limit = jobs.clamp(self.min, self.max); }
+    }
+
+    async fn acquire(&self) -> JobSlot<'_> {
+        loop {
+            // made before the check, so a slot freed in between still wakes us
+            let freed = self.freed.notified();
+            {
+                let mut s = self.state.lock().unwrap();
+                if s.in_flight < s.limit.max(1) {
+                    s.in_flight += 1;
+                    return JobSlot(self);
+                }
+            }
+            freed.await;
+        }
+    }
+
+    // Every request of the same wave sees about the same in_flight, so a burst
+    // of 429s halves the limit once rather than once each. Returns the new
+    // limit when it went down.
+    fn rate_limited(&self) -> Option<usize> {
+        let mut s = self.state.lock().unwrap();
+        let cut = (s.in_flight / 2).max(self.min);
+        s.clean = 0;
+        if cut >= s.limit { return None; }
+        s.limit = cut;
+        Some(cut)
+    }
+
+    fn clean(&self) {
+        let mut s = self.state.lock().unwrap();
+        s.clean += 1;
+        if s.clean >= s.limit && s.limit < self.max {
+            s.limit += 1;
+            s.clean = 0;
+            drop(s);
+            self.freed.notify_waiters();
+        }
+    }
+}
+
+// One ayah's place under the AdaptiveJobs limit, given back on drop
+struct JobSlot<'a>(&'a AdaptiveJobs);
+
+impl Drop for JobSlot<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().in_flight -= 1;
+        self.0.freed.notify_waiters();
+    }
+}
+
+/// Names for the ID3 tags, resolved by the caller
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub surah: String,
+    pub reciter: String,
+}
+
+// title "Al-Fatihah 3", artist = reciter, album = surah, track = ayah
+fn write_tags(mp3: &Path, info: &TagInfo, ayah: u32) -> anyhow::Result<()> {
+    use id3::TagLike;
+    let mut tag = id3::Tag::read_from_path(mp3).unwrap_or_default();
+    tag.set_title(format!("{} {}", info.surah, ayah));
+    tag.set_artist(&info.reciter);
+    tag.set_album(&info.surah);
+    tag.set_track(ayah);
+    tag.set_genre("Quran");
+    tag.write_to_path(mp3, id3::Version::Id3v24)?;
+    Ok(())
+}
+
+fn has_our_tags(mp3: &Path) -> bool {
+    use id3::TagLike;
+    id3::Tag::read_from_path(mp3).is_ok_and(|t| t.genre() == Some("Quran"))
+}
+
+/// One finished download
+#[derive(Debug, Clone, Copy)]
+pub struct FileTiming {
+    pub bytes: u64,
+    pub elapsed: std::time::Duration,
+}
+
+/// What `benchmark` prints
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub files: usize,
+    pub bytes: u64,
+    pub wall_ms: u64,
+    pub min_ms: u64,
+    pub max_ms: u64,
+    pub avg_ms: u64,
+    /// total bytes over wall time, in MB (10^6) per second
+    pub mb_per_s: f64,
+}
+
+impl BenchReport {
+    pub fn new(timings: &[FileTiming], wall: std::time::Duration) -> Self {
+        let ms: Vec<u64> = timings.iter().map(|t| t.elapsed.as_millis() as u64).collect();
+        let bytes = timings.iter().map(|t| t.bytes).sum();
+        let secs = wall.as_secs_f64();
+        BenchReport {
+            files: timings.len(),
+            bytes,
+            wall_ms: wall.as_millis() as u64,
+            min_ms: ms.iter().copied().min().unwrap_or(0),
+            max_ms: ms.iter().copied().max().unwrap_or(0),
+            avg_ms: if ms.is_empty() { 0 } else { ms.iter().sum::<u64>() / ms.len() as u64 },
+            mb_per_s: if secs > 0.0 { bytes as f64 / 1e6 / secs } else { 0.0 },
+        }
+    }
+}
+
+/// What `--convert` turns the downloaded mp3s into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convert {
+    Opus,
+    /// Vorbis in .ogg
+    Ogg,
+    /// AAC in .m4a
+    M4a,
+}
+
+impl Convert {
+    pub fn ext(self) -> &'static str {
+        match self {
+            Convert::Opus => "opus",
+            Convert::Ogg => "ogg",
+            Convert::M4a => "m4a",
+        }
+    }
+
+    fn codec(self) -> &'static [&'static str] {
+        match self {
+            Convert::Opus => &["-c:a", "libopus", "-b:a", "48k"],
+            Convert::Ogg => &["-c:a", "libvorbis", "-q:a", "3"],
+            // the edit list tells players how much encoder priming to skip;
+            // Opus and Vorbis carry that in their own headers
+            Convert::M4a => &["-c:a", "aac", "-b:a", "64k", "-use_editlist", "1"],
+        }
+    }
+}
+
+// --convert: NNN.mp3 → NNN.<ext> through a .part file, tags carried over
+fn convert_copy(src: &Path, dst: &Path, to: Convert) -> anyhow::Result<()> {
+    let part = part_path(dst);
+    let status = crate::ffmpeg::ffmpeg()
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(src)
+        .args(["-vn", "-map_metadata", "0"])
+        .args(to.codec())
+        .args(["-f", match to { Convert::M4a => "ipod", Convert::Opus => "opus", Convert::Ogg => "ogg" }])
+        .arg(&part)
+        .status()
+        .map_err(crate::error::HifzrError::ffmpeg_spawn)?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&part);
+        anyhow::bail!("ffmpeg failed converting {}", src.display());
+    }
+    std::fs::rename(&part, dst)?;
+    Ok(())
+}
+
+/// Two-pass EBU R128 `loudnorm` of an mp3 to `lufs` integrated loudness,
+/// in place: measure, then apply the measured values through a .part file.
+/// The sample rate and tags are kept.
+pub fn normalize_loudness(mp3: &Path, lufs: f64) -> anyhow::Result<()> {
+    #[derive(Deserialize)]
+    struct Measured { input_i: String, input_tp: String, input_lra: String, input_thresh: String, target_offset: String }
+    let target = format!("I={lufs}:TP=-1.5:LRA=11");
+    let out = crate::ffmpeg::ffmpeg()
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(mp3)
+        .args(["-vn", "-af", &format!("loudnorm={target}:print_format=json"), "-f", "null", "-"])
+        .output()
+        .map_err(crate::error::HifzrError::ffmpeg_spawn)?;
+    let log = String::from_utf8_lossy(&out.stderr);
+    // the measurement is the last {...} block ffmpeg prints
+    let json = log.rfind('{').zip(log.rfind('}')).filter(|(a, b)| a < b).map(|(a, b)| &log[a..=b]);
+    let m: Measured = match json.filter(|_| out.status.success()) {
+        Some(j) => serde_json::from_str(j).with_context(|| format!("odd loudnorm output for {}", mp3.display()))?,
+        None => anyhow::bail!("ffmpeg couldn't measure the loudness of {}", mp3.display()),
+    };
+    // loudnorm works at 192 kHz; write the original rate back out
+    let rate = crate::probe::probe(mp3).ok().and_then(|i| i.sample_rate).unwrap_or(44_100);
+    let filter = format!(
+        "loudnorm={target}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+        m.input_i, m.input_tp, m.input_lra, m.input_thresh, m.target_offset,
+    );
+    let part = part_path(mp3);
+    let status = crate::ffmpeg::ffmpeg()
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(mp3)
+        .args(["-vn", "-map_metadata", "0", "-af", &filter, "-ar", &rate.to_string()])
+        .args(crate::ffmpeg::MP3_GAPLESS)
+        .args(["-f", "mp3"])
+        .arg(&part)
+        .status()
+        .map_err(crate::error::HifzrError::ffmpeg_spawn)?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&part);
+        anyhow::bail!("ffmpeg failed normalizing {}", mp3.display());
+    }
+    std::fs::rename(&part, mp3)?;
+    Ok(())
+}
+
+// An earlier --convert run's copy of `stem`, standing in for a removed mp3
+fn converted_audio(dir: &Path, stem: &str) -> Option<PathBuf> {
+    crate::hifz::AUDIO_EXTS.iter()
+        .filter(|x| **x != "mp3")
+        .map(|x| dir.join(format!("{stem}.{x}")))
+        .find(|p| p.is_file())
+}
+
+// The mp3 or a converted copy is already there
+fn have_audio(dir: &Path, stem: &str) -> bool {
+    dir.join(format!("{stem}.mp3")).exists() || converted_audio(dir, stem).is_some()
+}
+
+// small speech-friendly Opus for the phone; the mp3 stays untouched
+fn opus_copy(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    let status = crate::ffmpeg::ffmpeg()
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(src)
+        .args(["-vn", "-ac", "1", "-c:a", "libopus", "-b:a", "24k", "-application", "voip"])
+        .arg(dst)
+        .status()
+        .map_err(crate::error::HifzrError::ffmpeg_spawn)?;
+    if !status.success() {
+        let _ = std::fs::remove_file(dst);
+        anyhow::bail!("ffmpeg failed on {}", src.display());
+    }
+    Ok(())
+}
+
+/// Cut `<stem>.mp3` (or its `--convert` copy) into `words/<stem>_<word>.mp3`
+/// clips (same extension as the source) from the timings in
+/// `<stem>.segments.json`; existing clips are kept unless `force`. Returns
+/// how many clips were written.
+pub fn slice_words(dir: &Path, stem: &str, force: bool) -> anyhow::Result<usize> {
+    let mp3 = converted_audio(dir, stem).unwrap_or_else(|| dir.join(format!("{stem}.mp3")));
+    let ext = mp3.extension().and_then(|x| x.to_str()).unwrap_or("mp3").to_string();
+    let data = std::fs::read(dir.join(format!("{stem}.segments.json")))?;
+    let segs: Vec<crate::models::Segment> = serde_json::from_slice(&data)?;
+    let words = dir.join("words");
+    std::fs::create_dir_all(&words)?;
+    let mut written = 0;
+    for (n, s) in segs.iter().enumerate() {
+        // j is the 1-based word number; plain [start, end] timings count along
+        let word = s.j.unwrap_or(n as u32 + 1);
+        let clip = words.join(format!("{stem}_{word}.{ext}"));
+        if !force && clip.exists() { continue; }
+        let secs = |ms: u32| format!("{}.{:03}", ms / 1000, ms % 1000);
+        let status = crate::ffmpeg::ffmpeg()
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-ss", &secs(s.start_ms), "-to", &secs(s.end_ms), "-i"])
+            .arg(&mp3)
+            .args(["-vn", "-c", "copy"])
+            .arg(&clip)
+            .status()
+            .map_err(crate::error::HifzrError::ffmpeg_spawn)?;
+        if !status.success() {
+            let _ = std::fs::remove_file(&clip);
+            anyhow::bail!("ffmpeg failed cutting word {} of {}", word, mp3.display());
+        }
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// manifest.json in a download folder: where every ayah came from and what
+/// it hashed to, for `hifzr verify`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub reciter_id: u32,
+    pub reciter_name: String,
+    pub source: String,
+    pub ayahs: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub verse_key: String,
+    pub ayah: u32,
+    /// File name inside the folder, e.g. "001.mp3"
+    pub file: String,
+    pub url: String,
+    pub bytes: u64,
+    pub sha256: String,
+    pub segments: usize,
+}
+
+pub fn read_manifest(dir: &Path) -> Option<Manifest> {
+    let text = std::fs::read_to_string(dir.join("manifest.json")).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// SHA-256 of a file on disk, as lowercase hex
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    use sha2::Digest;
+    use std::io::Read;
+    let mut h = sha2::Sha256::new();
+    let mut f = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        match f.read(&mut buf)? {
+            0 => break,
+            n => h.update(&buf[..n]),
+        }
+    }
+    Ok(hex(&h.finalize()))
+}
+
+/// What `hifzr verify` found in a surah folder
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    pub expected: u32,
+    pub present: usize,
+    pub missing: Vec<u32>,
+    /// zero-byte mp3s
+    pub empty: Vec<u32>,
+    /// size or SHA-256 no longer what manifest.json recorded
+    pub changed: Vec<u32>,
+}
+
+impl VerifyReport {
+    /// Every ayah that needs fetching again, in order
+    pub fn gaps(&self) -> Vec<u32> {
+        let mut v: Vec<u32> = self.missing.iter().chain(&self.empty).chain(&self.changed).copied().collect();
+        v.sort_unstable();
+        v
+    }
+}
+
+// Check `dir` for ayahs 1..=expected; files the manifest knows are re-hashed
+pub fn verify_folder(dir: &Path, expected: u32) -> anyhow::Result<VerifyReport> {
+    let have = if dir.is_dir() { crate::hifz::detect_available_ayahs(dir)? } else { Vec::new() };
+    let known: std::collections::HashMap<String, ManifestEntry> = read_manifest(dir)
+        .map(|m| m.ayahs.into_iter().map(|e| (e.file.clone(), e)).collect())
+        .unwrap_or_default();
+    let mut report = VerifyReport { expected, ..Default::default() };
+    for ayah in 1..=expected {
+        if !have.contains(&ayah) {
+            report.missing.push(ayah);
+            continue;
+        }
+        report.present += 1;
+        let path = crate::hifz::ayah_mp3(dir, ayah, 0);
+        let file = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let bytes = std::fs::metadata(&path)?.len();
+        if bytes == 0 {
+            report.empty.push(ayah);
+        } else if let Some(e) = known.get(&file)
+            && (e.bytes != bytes || sha256_file(&path)? != e.sha256) {
+            report.changed.push(ayah);
+        }
+    }
+    Ok(report)
+}
+
+/// One ayah that failed under --keep-going, as stored in retry.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedFetch {
+    pub verse_key: String,
+    pub reciter: u32,
+    pub url: String,
+    pub path: PathBuf,
+    pub error: String,
+    /// What the original run would have tagged the file with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<TagInfo>,
+    /// And the loudness it would have normalized it to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<f64>,
+}
+
+/// Some ayahs failed under --keep-going; the details are in `retry_file`
+#[derive(Debug)]
+pub struct SomeFailed {
+    pub failed: usize,
+    pub retry_file: PathBuf,
+}
+
+impl std::fmt::Display for SomeFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ayah(s) failed; run `hifzr retry {}`", self.failed, self.retry_file.display())
+    }
+}
+
+impl std::error::Error for SomeFailed {}
+
+fn read_retry_file(path: &Path) -> anyhow::Result<Vec<FailedFetch>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))
+}
+
+// an empty list removes the file, so a clean run leaves no stale retry.json
+fn write_retry_file(path: &Path, failed: &[FailedFetch]) -> anyhow::Result<()> {
+    if failed.is_empty() {
+        if path.exists() { std::fs::remove_file(path)?; }
+        return Ok(());
+    }
+    std::fs::write(path, serde_json::to_vec_pretty(failed)?)?;
+    Ok(())
+}
+
+// `hifzr retry`: fetch exactly the entries of a retry.json again, keeping
+// the ones that still fail. Returns (fixed, still failing).
+pub async fn retry_failed(client: &reqwest::Client, retry_file: &Path, opts: &FilterOpts) -> anyhow::Result<(usize, usize)> {
+    let entries = read_retry_file(retry_file)?;
+    let mut left = Vec::new();
+    let mut fixed = 0usize;
+    for mut e in entries {
+        if opts.deadline.is_some_and(|d| Instant::now() >= d) {
+            left.push(e);
+            continue;
+        }
+        if let Some(dir) = e.path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        // a failed fetch only ever leaves a .part (and cleans that up), so an
+        // mp3 already at the path stays as it is
+        match fetch_to(client, &e.url, &e.path, opts, opts.resume_partial, None).await {
+            Ok(h) => {
+                let ayah = e.verse_key.rsplit(':').next().and_then(|a| a.parse().ok()).unwrap_or(0);
+                let tags = e.tags.as_ref().map(|t| (t, ayah));
+                let sha256 = finish_download(&e.path, h, e.normalize, tags, |m| emit(&opts.progress, Event::Warning(m))).await?;
+                record_in_manifest(&e, ayah, sha256).await?;
+                fixed += 1;
+                emit(&opts.progress, Event::Note(format!("ok   {}", e.verse_key)));
+            }
+            Err(err) if err.downcast_ref::<RateLimited>().is_some() => return Err(err),
+            Err(err) => {
+                emit(&opts.progress, Event::Note(format!("fail {}: {:#}", e.verse_key, err)));
+                e.error = format!("{:#}", err);
+                left.push(e);
+            }
+        }
+    }
+    write_retry_file(retry_file, &left)?;
+    Ok((fixed, left.len()))
+}
+
+// Put a retried file into its folder's manifest.json like run_source would
+async fn record_in_manifest(e: &FailedFetch, ayah: u32, sha256: String) -> anyhow::Result<()> {
+    let Some(dir) = e.path.parent() else { return Ok(()) };
+    let file = e.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let bytes = fs::metadata(&e.path).await?.len();
+    let stem = e.path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let segments = std::fs::read(dir.join(format!("{stem}.segments.json"))).ok()
+        .and_then(|d| serde_json::from_slice::<Vec<serde_json::Value>>(&d).ok())
+        .map_or(0, |s| s.len());
+    let mut manifest = read_manifest(dir).unwrap_or_else(|| Manifest {
+        reciter_id: e.reciter,
+        reciter_name: e.tags.as_ref().map(|t| t.reciter.clone()).unwrap_or_default(),
+        ..Default::default()
+    });
+    manifest.ayahs.retain(|a| a.file != file);
+    manifest.ayahs.push(ManifestEntry { verse_key: e.verse_key.clone(), ayah, file, url: e.url.clone(), bytes, sha256, segments });
+    manifest.ayahs.sort_by(|a, b| a.file.cmp(&b.file));
+    write_atomic(&dir.join("manifest.json"), &serde_json::to_vec_pretty(&manifest)?).await?;
+    Ok(())
+}
+
+// "50m", "1.5g", "800k", "1024" → bytes (binary units)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim().to_ascii_lowercase();
+    let t = t.strip_suffix('b').unwrap_or(&t);
+    let (num, mult) = match t.chars().last() {
+        Some('k') => (&t[..t.len() - 1], 1u64 << 10),
+        Some('m') => (&t[..t.len() - 1], 1 << 20),
+        Some('g') => (&t[..t.len() - 1], 1 << 30),
+        _ => (t, 1),
+    };
+    let n: f64 = num.trim().parse().map_err(|_| format!("bad size {s:?} (try 50m, 1g)"))?;
+    if !(n > 0.0 && n.is_finite()) { return Err(format!("size must be positive, got {s:?}")); }
+    Ok((n * mult as f64) as u64)
+}
+
+/// The --deadline passed before every wanted ayah was fetched
+#[derive(Debug)]
+pub struct DeadlineHit {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl std::fmt::Display for DeadlineHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.total == 0 {
+            write!(f, "deadline reached while fetching verse metadata")
+        } else {
+            write!(f, "deadline reached after {}/{} ayahs", self.done, self.total)
+        }
+    }
+}
+
+impl std::error::Error for DeadlineHit {}
 
 
 fn base_dir(root: &str) -> PathBuf {
     Path::new(root).to_path_buf()
 }
 
+// Absolute URLs are trusted as-is; only truly relative paths get the default
+// CDN. Some reciters live on other hosts and come back as "//host/…" or
+// "host.tld/…" rather than a path under the CDN (audio.qurancdn.com unless
+// HIFZR_CDN_BASE says otherwise).
 fn resolve_audio_url(u: &str) -> String {
-    if u.starts_with("http") { u.to_string() } else { format!("https://audio.qurancdn.com/{}", u.trim_start_matches('/')) }
+    if u.starts_with("https://") || u.starts_with("http://") { return u.to_string(); }
+    if let Some(rest) = u.strip_prefix("//") { return format!("https://{}", rest); }
+    let path = u.trim_start_matches('/');
+    // "mirrors.quranicaudio.com/…": a host, not a folder (ends in a TLD-ish label)
+    if !u.starts_with('/')
+        && let Some((first, _)) = path.split_once('/')
+        && let Some((_, tld)) = first.rsplit_once('.')
+        && tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic()) {
+        return format!("https://{}", path);
+    }
+    format!("{}/{}", crate::api::endpoints().cdn, path)
+}
+
+// "…/001001.mp3?x=1" → "mp3"
+fn audio_ext(u: &str) -> String {
+    let path = u.split(['?', '#']).next().unwrap_or(u);
+    let file = path.rsplit('/').next().unwrap_or(path);
+    match file.rsplit_once('.') {
+        Some((_, ext)) if !ext.is_empty() => ext.to_ascii_lowercase(),
+        _ => "unknown".to_string(),
+    }
+}
+
+// Segment timings should be non-empty, in order, non-overlapping and end
+// within the file. One message per problem found.
+fn segment_issues(segs: &[crate::models::Segment], duration_ms: Option<u32>) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut prev_end = 0u32;
+    for (n, s) in segs.iter().enumerate() {
+        if s.end_ms <= s.start_ms {
+            out.push(format!("#{} is empty or reversed ({}–{} ms)", n + 1, s.start_ms, s.end_ms));
+        }
+        if s.start_ms < prev_end {
+            out.push(format!("#{} starts at {} ms, before #{} ends at {} ms", n + 1, s.start_ms, n, prev_end));
+        }
+        prev_end = prev_end.max(s.end_ms);
+    }
+    if let Some(d) = duration_ms
+        && prev_end > d {
+        out.push(format!("last segment ends at {} ms, past the {} ms file", prev_end, d));
+    }
+    out
+}
+
+// "001.mp3" → "001.mp3.part", where --resume-partial keeps unfinished bytes
+fn part_path(mp3: &Path) -> PathBuf {
+    let mut name = mp3.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    mp3.with_file_name(name)
+}
+
+// ".001.mp3.done" next to a file we rewrote after downloading it (loudnorm,
+// tags), holding its SHA-256: it no longer matches the CDN's size but is whole
+fn done_marker(mp3: &Path) -> PathBuf {
+    let name = mp3.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    mp3.with_file_name(format!(".{name}.done"))
+}
+
+// A local file smaller than what the CDN says it should be was cut off,
+// unless its done marker or manifest entry vouches for exactly these bytes.
+// Deliberately no retries: offline, every existing file just counts as complete.
+async fn is_truncated(client: &reqwest::Client, url: &str, mp3: &Path, known: Option<&ManifestEntry>) -> bool {
+    let Ok(local) = fs::metadata(mp3).await.map(|m| m.len()) else { return false };
+    // our tags go on only after a complete download (and change the size)
+    if has_our_tags(mp3) { return false; }
+    let short = match client.head(url).send().await {
+        Ok(r) if r.status().is_success() => head_length(&r).is_some_and(|len| local < len),
+        _ => false,
+    };
+    if !short { return false; }
+    let recorded = match fs::read_to_string(done_marker(mp3)).await {
+        Ok(h) => Some(h.trim().to_string()),
+        Err(_) => known.filter(|e| e.bytes == local).map(|e| e.sha256.clone()),
+    };
+    let Some(recorded) = recorded else { return true };
+    let path = mp3.to_path_buf();
+    match tokio::task::spawn_blocking(move || sha256_file(&path)).await {
+        Ok(Ok(h)) => h != recorded,
+        _ => true,
+    }
+}
+
+// Content-Length of a HEAD response; reqwest's content_length() reports the
+// (empty) body there, not the header
+fn head_length(r: &reqwest::Response) -> Option<u64> {
+    r.headers().get(reqwest::header::CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+// Write through a .part sibling and rename, so a file under its real name is
+// always complete
+async fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let part = part_path(path);
+    fs::write(&part, data).await?;
+    fs::rename(&part, path).await
+}
+
+// Streams into NNN.mp3.part and renames it once complete, so an interrupted
+// run never leaves a short NNN.mp3 that later looks done. `resume`: keep the
+// .part on failure and pick up where an earlier attempt stopped. `file`:
+// the name its bytes are reported under, if any.
+// Returns the SHA-256 of what was saved
+async fn fetch_to(client: &reqwest::Client, url: &str, mp3: &Path, opts: &FilterOpts, resume: bool, file: Option<&str>) -> anyhow::Result<String> {
+    let part = part_path(mp3);
+    let res = fetch_part(client, url, &part, opts, resume, file).await;
+    match res {
+        Ok(hash) => {
+            fs::rename(&part, mp3).await?;
+            Ok(hash)
+        }
+        Err(e) => {
+            if !resume { let _ = fs::remove_file(&part).await; }
+            Err(e)
+        }
+    }
+}
+
+async fn fetch_part(client: &reqwest::Client, url: &str, target: &Path, opts: &FilterOpts, resume: bool, file: Option<&str>) -> anyhow::Result<String> {
+    use sha2::Digest;
+    let mut have = if resume {
+        fs::metadata(target).await.map(|m| m.len()).unwrap_or(0)
+    } else {
+        0
+    };
+
+    let resp = loop {
+        let mut req = client.get(url);
+        if have > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-", have));
+        }
+        let resp = crate::api::send_noting_429s(req, &opts.retry, || {
+            if let Some(cut) = opts.adaptive_jobs.as_ref().and_then(|a| a.rate_limited()) {
+                emit(&opts.progress, Event::Note(format!("rate limited; down to {} ayah(s) at once", cut)));
+            }
+        }).await?;
+        // a leftover that doesn't fit the file any more: start over
+        if have > 0 && resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            let _ = fs::remove_file(target).await;
+            have = 0;
+            continue;
+        }
+        break resp;
+    };
+    if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS && opts.retry.stop_on_429 {
+        return Err(RateLimited { url: url.to_string() }.into());
+    }
+    let resp = resp.error_for_status()
+        .with_context(|| format!("GET {}", url))?;
+    if let Some(a) = &opts.adaptive_jobs { a.clean(); }
+    let resumed = have > 0 && resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    if have > 0 && !resumed {
+        emit(&opts.progress, Event::Note(format!("no range support for {}; downloading it again", url)));
+        have = 0;
+    }
+    // a 200 carrying an HTML/JSON error page must not become "NNN.mp3"
+    if let Some(ct) = resp.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        let ct = ct.to_ascii_lowercase();
+        if ct.starts_with("text/") || ct.contains("json") || ct.contains("xml") {
+            anyhow::bail!("{} answered with {} instead of audio", url, ct);
+        }
+    }
+    let max = opts.max_file_size.unwrap_or(u64::MAX);
+    if let Some(len) = resp.content_length()
+        && len + have > max {
+        anyhow::bail!("{} is {} bytes, over the {} byte --max-file-size", url, len + have, max);
+    }
+    let mut f = if resumed {
+        fs::OpenOptions::new().append(true).open(target).await?
+    } else {
+        fs::File::create(target).await?
+    };
+    let total = resp.content_length().map(|len| len + have);
+    let report = |done| if let Some(f) = file {
+        emit(&opts.progress, Event::FileBytes { file: f.to_string(), done, total });
+    };
+    report(have);
+    // hash as the bytes go by; a resumed file's head has to be read once
+    let mut hasher = sha2::Sha256::new();
+    if resumed {
+        hasher.update(fs::read(target).await?);
+    }
+    let mut s = resp.bytes_stream();
+    let mut written = have;
+    let mut head: Vec<u8> = Vec::new();
+    while let Some(chunk) = s.next().await {
+        let chunk = chunk?;
+        // sniff the first bytes too; some CDNs mislabel everything octet-stream
+        if !resumed && head.len() < 16 {
+            head.extend(chunk.iter().take(16 - head.len()));
+            if head.len() >= 16 && !crate::probe::looks_like_audio(&head) {
+                drop(f);
+                let _ = fs::remove_file(target).await;
+                anyhow::bail!("{} doesn't look like audio (starts with {:?})", url, String::from_utf8_lossy(&head));
+            }
+        }
+        written += chunk.len() as u64;
+        if written > max {
+            drop(f);
+            let _ = fs::remove_file(target).await;
+            anyhow::bail!("{} passed the {} byte --max-file-size; aborted", url, max);
+        }
+        f.write_all(&chunk).await?;
+        hasher.update(&chunk);
+        report(written);
+        if let Some(rate) = &opts.max_rate { rate.take(chunk.len() as u64).await; }
+    }
+    if !resumed && head.len() < 16 && !crate::probe::looks_like_audio(&head) {
+        drop(f);
+        let _ = fs::remove_file(target).await;
+        anyhow::bail!("{} returned {} bytes that aren't audio", url, head.len());
+    }
+    f.flush().await?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// What to download: a surah (the usual case) or any other verse listing
+#[derive(Debug, Clone, Copy)]
+pub enum Source<'a> {
+    Chapter(u32),
+    /// API path like "verses/by_juz/30", or a full URL
+    Collection(&'a str),
+}
+
+impl Source<'_> {
+    /// The API path (or URL) the verses come from
+    pub fn path(&self) -> String {
+        match self {
+            Source::Chapter(c) => format!("verses/by_chapter/{c}"),
+            Source::Collection(c) => c.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Source<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Chapter(c) => write!(f, "surah {}", c),
+            Source::Collection(c) => write!(f, "collection {}", c),
+        }
+    }
+}
+
+/// What `download --count` reports for one source
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Count {
+    pub ayahs: usize,
+    pub bytes: u64,          // sum of HEAD content-lengths
+    pub unknown_size: usize, // ayahs the CDN wouldn't size
+}
+
+// Resolve the selection and HEAD each file; nothing is written
+pub async fn count_source(
+    client: &reqwest::Client,
+    reciter: u32,
+    source: Source<'_>,
+    opts: &FilterOpts,
+) -> anyhow::Result<Count> {
+    let verses = match source {
+        Source::Chapter(c) => crate::api::fetch_chapter(client, reciter, c, &opts.retry).await,
+        Source::Collection(c) => crate::api::fetch_collection(client, reciter, c, &opts.retry).await,
+    }.with_context(|| format!("fetching verses failed for {}", source))?;
+    let mut count = Count::default();
+    for v in verses {
+        if opts.only_verses.as_ref().is_some_and(|w| !w.contains(&v.verse_number)) { continue; }
+        if v.audio.url.trim().is_empty() { continue; }
+        if opts.require_segments && v.audio.segments.as_ref().is_none_or(|s| s.is_empty()) { continue; }
+        count.ayahs += 1;
+        let url = resolve_audio_url(&v.audio.url);
+        let len = match crate::api::send_retrying(client.head(&url), &opts.retry).await {
+            Ok(r) if r.status().is_success() => head_length(&r),
+            _ => None,
+        };
+        match len {
+            Some(n) => count.bytes += n,
+            None => count.unknown_size += 1,
+        }
+        emit(&opts.progress, Event::Sizing { source: source.to_string(), ayahs: count.ayahs });
+    }
+    emit(&opts.progress, Event::Finished);
+    Ok(count)
+}
+
+/// Fetch the verse listings of `chapters` in the background, up to `ahead`
+/// at once, and hand them over in order, so a multi-surah download doesn't
+/// wait on the API before each surah. A listing that fails arrives as None;
+/// run_filter then asks for it itself.
+pub fn prefetch_chapters(
+    client: &reqwest::Client,
+    reciter: u32,
+    chapters: Vec<u32>,
+    opts: &FilterOpts,
+    ahead: usize,
+) -> tokio::sync::mpsc::Receiver<Option<std::sync::Arc<Vec<crate::models::Verse>>>> {
+    let ahead = ahead.max(1);
+    let (tx, rx) = tokio::sync::mpsc::channel(ahead);
+    let (client, translation, retry) = (client.clone(), opts.translation, opts.retry.clone());
+    tokio::spawn(async move {
+        let (client, retry) = (&client, &retry);
+        let mut listings = futures_util::stream::iter(chapters)
+            .map(|c| async move {
+                let path = Source::Chapter(c).path();
+                crate::api::fetch_collection_translated(client, reciter, &path, translation, retry).await
+                    .map_err(|e| emit(&retry.progress, Event::Note(format!("prefetching surah {c} failed ({e}); it's fetched again when its turn comes"))))
+                    .ok()
+                    .map(std::sync::Arc::new)
+            })
+            .buffered(ahead);
+        while let Some(listing) = listings.next().await {
+            if tx.send(listing).await.is_err() { break; }
+        }
+    });
+    rx
 }
 
 pub async fn run_filter(
@@ -17,59 +960,689 @@ pub async fn run_filter(
     reciter: u32,
     chapter: u32,
     out_root: &str,
-    force: bool,
-    only_verses: Option<&[u32]>,
+    opts: &FilterOpts,
+) -> crate::error::Result<()> {
+    run_source(client, reciter, Source::Chapter(chapter), out_root, opts).await
+}
+
+/// Download one source into `out_root`. `SomeFailed`, `DeadlineHit` and
+/// `RateLimited` arrive as `HifzrError::Other`; `into_anyhow` keeps them
+/// downcastable.
+pub async fn run_source(
+    client: &reqwest::Client,
+    reciter: u32,
+    source: Source<'_>,
+    out_root: &str,
+    opts: &FilterOpts,
+) -> crate::error::Result<()> {
+    Ok(run_source_any(client, reciter, source, out_root, opts).await?)
+}
+
+async fn run_source_any(
+    client: &reqwest::Client,
+    reciter: u32,
+    source: Source<'_>,
+    out_root: &str,
+    opts: &FilterOpts,
 ) -> anyhow::Result<()> {
 
     //let dir = chapter_dir(out_root, chapter);
     let dir = base_dir(out_root);
-    fs::create_dir_all(&dir).await?;
+    // no ffmpeg: say so once and just fetch the mp3s, which are still worth having
+    let plain;
+    let needs: Vec<&str> = [
+        (opts.convert.is_some(), "--convert"),
+        (opts.normalize.is_some(), "--normalize"),
+        (opts.words, "--words"),
+        (opts.phone_copy.is_some(), "--phone-copy"),
+    ].into_iter().filter_map(|(on, flag)| on.then_some(flag)).collect();
+    let opts = if !needs.is_empty() && !opts.dry_run && crate::ffmpeg::detect_ffmpeg().is_none() {
+        crate::ffmpeg::warn_missing(&needs.join("/"), &opts.progress);
+        plain = FilterOpts { convert: None, delete_source: false, normalize: None, words: false, phone_copy: None, ..opts.clone() };
+        &plain
+    } else {
+        opts
+    };
+    if !opts.dry_run {
+        fs::create_dir_all(&dir).await?;
+        if let Some(p) = &opts.phone_copy {
+            fs::create_dir_all(p).await?;
+        }
+    }
+
+    let verses = match &opts.verses {
+        Some(listed) => listed.as_ref().clone(),
+        None => {
+            let path = source.path();
+            let meta = crate::api::fetch_collection_translated(client, reciter, &path, opts.translation, &opts.retry);
+            match opts.deadline {
+                Some(d) => tokio::time::timeout_at(d, meta).await
+                    .map_err(|_| DeadlineHit { done: 0, total: 0 })?,
+                None => meta.await,
+            }.with_context(|| format!("fetching verses failed for {}", source))?
+        }
+    };
+
+    let wanted: Option<std::collections::HashSet<u32>> = opts.only_verses.as_ref().map(|v| v.iter().copied().collect());
+
+    // check coverage before fetching a single byte: asked-for ayahs that the
+    // listing lacks, or that this reciter has no audio for
+    let mut uncovered: Vec<String> = verses.iter()
+        .filter(|v| wanted.as_ref().is_none_or(|w| w.contains(&v.verse_number)))
+        .filter(|v| v.audio.url.trim().is_empty())
+        .map(|v| v.verse_key.clone())
+        .collect();
+    if let Some(w) = &opts.only_verses {
+        let listed: std::collections::HashSet<u32> = verses.iter().map(|v| v.verse_number).collect();
+        uncovered.extend(w.iter().filter(|a| !listed.contains(a)).map(|a| format!("ayah {}", a)));
+    }
+    if !uncovered.is_empty() {
+        if !opts.keep_going {
+            anyhow::bail!("reciter {} has no audio for {} of {}: {} (use --keep-going to fetch the rest)",
+                reciter, uncovered.len(), source, uncovered.join(", "));
+        }
+        emit(&opts.progress, Event::Warning(format!("reciter {} has no audio for {}; skipping them", reciter, uncovered.join(", "))));
+    }
+    let mut verses: Vec<_> = verses.into_iter().filter(|v| !v.audio.url.trim().is_empty()).collect();
+    if opts.require_segments {
+        let no_segs = |v: &crate::models::Verse| v.audio.segments.as_ref().is_none_or(|s| s.is_empty());
+        let excluded = verses.iter()
+            .filter(|v| wanted.as_ref().is_none_or(|w| w.contains(&v.verse_number)) && no_segs(v))
+            .count();
+        verses.retain(|v| !no_segs(v));
+        if excluded > 0 {
+            emit(&opts.progress, Event::Note(format!("--require-segments: skipping {} ayah(s) of {} without segment timings", excluded, source)));
+        }
+    }
+
+    let verses: Vec<_> = verses.into_iter()
+        .filter(|v| wanted.as_ref().is_none_or(|w| w.contains(&v.verse_number)))
+        .collect();
+    if opts.estimate {
+        let todo: Vec<_> = verses.iter()
+            .filter(|v| opts.force || !have_audio(&dir, &file_stem(source, v)))
+            .collect();
+        print_estimate(client, &todo, verses.len() - todo.len(), opts).await;
+    }
+    if opts.dry_run {
+        report_plan(&dir, source, &verses, opts);
+        return Ok(());
+    }
+    let mut formats: std::collections::BTreeMap<String, usize> = Default::default();
+    for v in &verses {
+        *formats.entry(audio_ext(&v.audio.url)).or_default() += 1;
+    }
+
+    // up to --jobs ayahs in flight; each writes only its own files, so the
+    // finishing order doesn't matter
+    let job = Job {
+        client, reciter, source, opts,
+        dir: &dir,
+        total: verses.len(),
+        done: AtomicUsize::new(0),
+        stop: AtomicBool::new(false),
+        old: read_manifest(&dir).map(|m| m.ayahs.into_iter().map(|e| (e.file.clone(), e)).collect()).unwrap_or_default(),
+    };
+    // earlier entries stay unless this run rewrites them
+    let mut entries: std::collections::BTreeMap<String, ManifestEntry> = job.old.iter()
+        .filter(|(f, _)| dir.join(f).exists())
+        .map(|(f, e)| (f.clone(), e.clone()))
+        .collect();
+    // with --max-jobs, the AdaptiveJobs limit says how many of these may run
+    let most = match &opts.adaptive_jobs {
+        Some(a) => {
+            a.start_at(opts.jobs);
+            a.max
+        }
+        None => opts.jobs.max(1),
+    };
+    emit(&opts.progress, Event::Started { total: job.total });
+    let mut steps = futures_util::stream::iter(verses)
+        .map(|v| async {
+            let _slot = match &opts.adaptive_jobs {
+                Some(a) => Some(a.acquire().await),
+                None => None,
+            };
+            prep_verse(&job, v).await
+        })
+        .buffer_unordered(most);
+    let mut seg_problems: Vec<(String, Vec<String>)> = Vec::new();
+    let mut failed: Vec<FailedFetch> = Vec::new();
+    let mut fatal: Option<anyhow::Error> = None;
+    // drain everything: a failure stops new ayahs from starting, but the ones
+    // already running finish (and clean up) on their own
+    while let Some(step) = steps.next().await {
+        match step {
+            Ok(Step::Done(issues, entry)) => {
+                seg_problems.extend(issues);
+                entries.insert(entry.file.clone(), entry);
+            }
+            Ok(Step::Failed(f)) => failed.push(f),
+            Ok(Step::Skipped) => {}
+            Err(e) => {
+                job.stop.store(true, Ordering::Relaxed);
+                if fatal.is_none() { fatal = Some(e); }
+            }
+        }
+    }
+    drop(steps);
+    emit(&opts.progress, Event::Finished);
+    if let Some(e) = fatal {
+        if let Some(d) = e.downcast_ref::<DeadlineHit>() {
+            return Err(DeadlineHit { done: job.done.load(Ordering::Relaxed), total: d.total }.into());
+        }
+        return Err(e);
+    }
+    failed.sort_by(|a, b| a.verse_key.cmp(&b.verse_key));
+    seg_problems.sort();
+    let manifest = Manifest {
+        reciter_id: reciter,
+        reciter_name: opts.tags.as_ref().map(|t| t.reciter.clone()).unwrap_or_default(),
+        source: source.to_string(),
+        ayahs: entries.into_values().collect(),
+    };
+    write_atomic(&dir.join("manifest.json"), &serde_json::to_vec_pretty(&manifest)?).await?;
+
+    let summary: Vec<String> = formats.iter().map(|(ext, n)| format!("{ext} ×{n}")).collect();
+    if !summary.is_empty() {
+        emit(&opts.progress, Event::Note(format!("formats: {}", summary.join(", "))));
+    }
+    if formats.len() > 1 {
+        emit(&opts.progress, Event::Warning(format!("{} mixes audio formats; concat/gapless playback may misbehave", source)));
+    }
+    if opts.verify_segments {
+        if seg_problems.is_empty() {
+            emit(&opts.progress, Event::Note("segments: ok".into()));
+        } else {
+            let count: usize = seg_problems.iter().map(|(_, i)| i.len()).sum();
+            emit(&opts.progress, Event::Note(format!("segments: {} issue(s) in {} ayah(s)", count, seg_problems.len())));
+            for (key, issues) in &seg_problems {
+                emit(&opts.progress, Event::Note(format!("  {}: {}", key, issues.join("; "))));
+            }
+        }
+    }
+    if opts.keep_going {
+        let retry_file = dir.join("retry.json");
+        write_retry_file(&retry_file, &failed)?;
+        if !failed.is_empty() {
+            return Err(SomeFailed { failed: failed.len(), retry_file }.into());
+        }
+    } else if !failed.is_empty() {
+        let list: Vec<String> = failed.iter().map(|f| format!("{}: {}", f.verse_key, f.error)).collect();
+        anyhow::bail!("{} ayah(s) failed:\n  {}", failed.len(), list.join("\n  "));
+    }
+    Ok(())
+}
+
+// Shared state for one run_source pass
+struct Job<'a> {
+    client: &'a reqwest::Client,
+    reciter: u32,
+    source: Source<'a>,
+    opts: &'a FilterOpts,
+    dir: &'a Path,
+    total: usize,
+    done: AtomicUsize,
+    /// set after a failure (without --keep-going) or a fatal error: start nothing new
+    stop: AtomicBool,
+    /// manifest.json from an earlier run, by file name
+    old: std::collections::HashMap<String, ManifestEntry>,
+}
+
+impl Job<'_> {
+    // only surah downloads are tagged
+    fn tags(&self) -> Option<&TagInfo> {
+        match self.source {
+            Source::Chapter(_) => self.opts.tags.as_ref(),
+            Source::Collection(_) => None,
+        }
+    }
+
+    fn note(&self, msg: String) {
+        emit(&self.opts.progress, Event::Note(msg));
+    }
+
+    fn warn(&self, msg: String) {
+        emit(&self.opts.progress, Event::Warning(msg));
+    }
+}
 
-    let verses = crate::api::fetch_chapter(client, reciter, chapter).await
-        .with_context(|| format!("fetch_chapter failed for surah {}", chapter))?;
+enum Step {
+    /// fetched or already there; with --verify-segments, any timing problems
+    Done(Option<(String, Vec<String>)>, ManifestEntry),
+    Failed(FailedFetch),
+    /// not started because the run is stopping
+    Skipped,
+}
 
-    let wanted: Option<std::collections::HashSet<u32>> = only_verses.map(|v| v.iter().copied().collect());
+// An ayah's file name without the extension, following the --name-template.
+// Collections can span surahs, so one without the surah in it names by
+// verse key ("078_001") there.
+fn file_stem(source: Source<'_>, v: &crate::models::Verse) -> String {
+    let t = crate::hifz::name_template();
+    let surah = v.verse_key.split_once(':').and_then(|(s, _)| s.parse::<u32>().ok());
+    match (source, surah) {
+        (Source::Chapter(c), _) => t.render(c, v.verse_number),
+        (Source::Collection(_), Some(s)) if t.uses_surah() => t.render(s, v.verse_number),
+        (Source::Collection(_), Some(s)) => format!("{:03}_{:03}", s, v.verse_number),
+        (Source::Collection(_), None) => format!("{:03}", v.verse_number),
+    }
+}
 
-    // tiny progress
-    let total = verses.len();
-    let mut done = 0usize;
+// --estimate: HEAD the files in parallel; ones the CDN won't size count as
+// the average of the rest
+async fn print_estimate(client: &reqwest::Client, todo: &[&crate::models::Verse], have: usize, opts: &FilterOpts) {
+    let retry = &opts.retry;
+    let sizes: Vec<Option<u64>> = futures_util::stream::iter(todo)
+        .map(|v| async move {
+            match crate::api::send_retrying(client.head(resolve_audio_url(&v.audio.url)), retry).await {
+                Ok(r) if r.status().is_success() => head_length(&r),
+                _ => None,
+            }
+        })
+        .buffer_unordered(opts.jobs.max(4))
+        .collect().await;
+    let known: Vec<u64> = sizes.iter().flatten().copied().collect();
+    let bytes = match known.len() {
+        0 => 0,
+        n => known.iter().sum::<u64>() * todo.len() as u64 / n as u64,
+    };
+    let mut line = format!("≈ {:.1} MB across {} ayahs", bytes as f64 / 1e6, todo.len());
+    if have > 0 { line += &format!(" ({} already downloaded)", have); }
+    if known.len() < todo.len() { line += &format!(" ({} without a size)", todo.len() - known.len()); }
+    emit(&opts.progress, Event::Note(line));
+}
 
+// --dry-run: what each ayah would do, with the URL it would come from
+fn report_plan(dir: &Path, source: Source<'_>, verses: &[crate::models::Verse], opts: &FilterOpts) {
+    let mut skip = 0;
     for v in verses {
-        if let Some(w) = &wanted {
-            if !w.contains(&v.verse_number) { continue; }
-        }
-
-        let ayah = v.verse_number;
-        let mp3 = dir.join(format!("{:03}.mp3", ayah));
-        let seg = dir.join(format!("{:03}.segments.json", ayah));
-
-        if force || !mp3.exists() {
-            let url = resolve_audio_url(&v.audio.url);
-            let resp = client.get(&url).send().await?
-                .error_for_status()
-                .with_context(|| format!("GET {}", url))?;
-            let mut f = fs::File::create(&mp3).await?;
-            let mut s = resp.bytes_stream();
-            while let Some(chunk) = s.next().await { f.write_all(&chunk?).await?; }
-        }
-
-        let pairs: Vec<[u32; 2]> = match v.audio.segments.as_ref() {
-            // If your model is: Option<Vec<Segment>>
-            Some(segs) => segs
-                .iter()
-                .filter_map(|s| {
-                    let (sms, ems) = (s.start_ms, s.end_ms);
-                    (ems > sms).then_some([sms, ems])
-                })
-                .collect(),
-            None => Vec::new(),
+        let stem = file_stem(source, v);
+        let mp3 = dir.join(format!("{stem}.mp3"));
+        let exists = !opts.force && have_audio(dir, &stem);
+        if exists { skip += 1; }
+        emit(&opts.progress, Event::Planned { path: mp3, url: resolve_audio_url(&v.audio.url), exists });
+    }
+    emit(&opts.progress, Event::PlanTotal { get: verses.len() - skip, skip });
+}
+
+// A fresh download's finishing touches, for run_source and `hifzr retry`
+// alike: normalize, then tag (the re-encode wouldn't keep our tags). Both
+// change the bytes, so the streamed hash goes stale and the size no longer
+// says whether the file is whole; the .done marker records the new hash.
+// Returns the file's hash.
+async fn finish_download(mp3: &Path, streamed: String, normalize: Option<f64>, tags: Option<(&TagInfo, u32)>, warn: impl Fn(String)) -> anyhow::Result<String> {
+    let mut rewritten = false;
+    if let Some(lufs) = normalize {
+        let path = mp3.to_path_buf();
+        match tokio::task::spawn_blocking(move || normalize_loudness(&path, lufs)).await? {
+            Ok(()) => rewritten = true,
+            Err(e) => warn(format!("couldn't normalize {}: {:#}", mp3.display(), e)),
+        }
+    }
+    // tagging is cosmetic; a file id3 can't handle is still a good download
+    if let Some((info, ayah)) = tags {
+        let (path, info) = (mp3.to_path_buf(), info.clone());
+        match tokio::task::spawn_blocking(move || write_tags(&path, &info, ayah)).await? {
+            Ok(()) => rewritten = true,
+            Err(e) => warn(format!("couldn't tag {}: {:#}", mp3.display(), e)),
+        }
+    }
+    if !rewritten {
+        let _ = fs::remove_file(done_marker(mp3)).await;
+        return Ok(streamed);
+    }
+    let path = mp3.to_path_buf();
+    let h = tokio::task::spawn_blocking(move || sha256_file(&path)).await??;
+    write_atomic(&done_marker(mp3), h.as_bytes()).await?;
+    Ok(h)
+}
+
+// Everything for one ayah: audio, tafsir, phone copy, segments.json.
+// Errors are fatal for the whole run; a failed GET comes back as Step::Failed.
+async fn prep_verse(job: &Job<'_>, v: crate::models::Verse) -> anyhow::Result<Step> {
+    let opts = job.opts;
+    if job.stop.load(Ordering::Relaxed) { return Ok(Step::Skipped); }
+    if opts.deadline.is_some_and(|d| Instant::now() >= d) {
+        return Err(DeadlineHit { done: 0, total: job.total }.into());
+    }
+
+    let ayah = v.verse_number;
+    let stem = file_stem(job.source, &v);
+    let mp3 = job.dir.join(format!("{stem}.mp3"));
+    let seg = job.dir.join(format!("{stem}.segments.json"));
+
+    let url = resolve_audio_url(&v.audio.url);
+    let mut failure = None;
+    let mut hash = None;
+    // the copy an earlier --convert --delete-source run left counts as downloaded
+    let kept = if opts.force || mp3.exists() { None } else { converted_audio(job.dir, &stem) };
+    // an interrupted earlier run can leave a short mp3 behind; continue it
+    // from where it stopped instead of trusting it or fetching it whole
+    let mp3_name = format!("{stem}.mp3");
+    let truncated = !opts.force && mp3.exists() && is_truncated(job.client, &url, &mp3, job.old.get(&mp3_name)).await;
+    if truncated {
+        job.note(format!("{} is incomplete; resuming it", mp3.display()));
+        fs::rename(&mp3, part_path(&mp3)).await?;
+    }
+    if opts.force || truncated || (!mp3.exists() && kept.is_none()) {
+        let started = Instant::now();
+        let file = opts.file_progress.then_some(mp3_name.as_str());
+        let fetch = fetch_to(job.client, &url, &mp3, opts, opts.resume_partial || truncated, file);
+        let res = match opts.deadline {
+            Some(d) => tokio::time::timeout_at(d, fetch).await.ok(),
+            None => Some(fetch.await),
         };
-        let data = serde_json::to_vec(&pairs)?;
-        tokio::fs::write(&seg, data).await?;
+        if file.is_some() { emit(&opts.progress, Event::FileDone { file: mp3_name.clone() }); }
+        match res {
+            Some(Ok(h)) => {
+                if let Some(t) = &opts.timings {
+                    let bytes = fs::metadata(&mp3).await.map(|m| m.len()).unwrap_or(0);
+                    let elapsed = started.elapsed();
+                    t.lock().unwrap().push(FileTiming { bytes, elapsed });
+                }
+                hash = Some(finish_download(&mp3, h, opts.normalize, job.tags().map(|t| (t, ayah)), |m| job.warn(m)).await?);
+            }
+            Some(Err(e)) if e.downcast_ref::<RateLimited>().is_some() => return Err(e),
+            Some(Err(e)) => {
+                job.note(format!("failed {}: {:#}", v.verse_key, e));
+                if !opts.keep_going { job.stop.store(true, Ordering::Relaxed); }
+                failure = Some(FailedFetch {
+                    verse_key: v.verse_key.clone(),
+                    reciter: job.reciter,
+                    url: url.clone(),
+                    path: mp3.clone(),
+                    error: format!("{:#}", e),
+                    tags: job.tags().cloned(),
+                    normalize: opts.normalize,
+                });
+            }
+            None => {
+                // cut off mid-stream: the .part is only worth keeping to resume
+                if !opts.resume_partial { let _ = fs::remove_file(part_path(&mp3)).await; }
+                return Err(DeadlineHit { done: 0, total: job.total }.into());
+            }
+        }
+    }
+    let ok = failure.is_none();
 
-        done += 1;
-        eprint!("\rprepping {:03}: {}/{}", ayah, done, total);
+    // tafsir is extra; its failure never fails the audio
+    if let Some(t) = opts.tafsir {
+        let txt = job.dir.join(format!("{stem}.tafsir.txt"));
+        if opts.force || !txt.exists() {
+            match crate::api::fetch_tafsir(job.client, t, &v.verse_key, &opts.retry).await {
+                Ok(text) => write_atomic(&txt, text.as_bytes()).await?,
+                Err(e) if e.downcast_ref::<RateLimited>().is_some() => return Err(e),
+                Err(e) => job.warn(format!("no tafsir for {}: {:#}", v.verse_key, e)),
+            }
+        }
+    }
+    // the translation came with the verse metadata; nothing more to fetch
+    if let Some(t) = opts.translation {
+        let txt = job.dir.join(format!("{stem}.translation.txt"));
+        match v.translations.iter().find(|tr| tr.resource_id == t || tr.resource_id == 0) {
+            Some(tr) if opts.force || !txt.exists() => write_atomic(&txt, crate::api::translation_text(&tr.text).as_bytes()).await?,
+            Some(_) => {}
+            None => job.warn(format!("no translation {} for {}", t, v.verse_key)),
+        }
+    }
+
+    // the file the folder keeps for this ayah: the converted one if asked for
+    let mut audio = kept.unwrap_or_else(|| mp3.clone());
+    if ok
+        && let Some(to) = opts.convert {
+        let dst = job.dir.join(format!("{stem}.{}", to.ext()));
+        if mp3.exists() && (opts.force || hash.is_some() || !dst.exists()) {
+            let (src, d) = (mp3.clone(), dst.clone());
+            if let Err(e) = tokio::task::spawn_blocking(move || convert_copy(&src, &d, to)).await? {
+                job.warn(format!("couldn't convert {}: {:#}", v.verse_key, e));
+            }
+        }
+        if dst.exists() { audio = dst; }
+    }
+
+    if ok
+        && let Some(phone) = &opts.phone_copy {
+        let opus = phone.join(format!("{stem}.opus"));
+        if opts.force || !opus.exists() {
+            let (src, dst) = (if mp3.exists() { mp3.clone() } else { audio.clone() }, opus.clone());
+            match tokio::task::spawn_blocking(move || opus_copy(&src, &dst)).await? {
+                Ok(()) => {}
+                Err(e) => job.warn(format!("no phone copy for {}: {:#}", v.verse_key, e)),
+            }
+        }
+    }
+
+    let mut seg_issues = None;
+    if ok && opts.verify_segments
+        && let Some(segs) = v.audio.segments.as_deref() {
+        let issues = segment_issues(segs, crate::probe::duration_ms(&audio));
+        if !issues.is_empty() { seg_issues = Some((v.verse_key.clone(), issues)); }
+    }
+
+    // word indices included, so --words (or `hifzr words`) can slice later
+    // without asking the API again
+    let pairs: Vec<&crate::models::Segment> = v.audio.segments.iter().flatten()
+        .filter(|s| s.end_ms > s.start_ms)
+        .collect();
+    let data = serde_json::to_vec(&pairs)?;
+    write_atomic(&seg, &data).await?;
+
+    if ok && opts.words {
+        let (dir, stem, force) = (job.dir.to_path_buf(), stem.clone(), opts.force);
+        if let Err(e) = tokio::task::spawn_blocking(move || slice_words(&dir, &stem, force)).await? {
+            job.warn(format!("no word clips for {}: {:#}", v.verse_key, e));
+        }
+    }
+
+    if let Some(f) = failure { return Ok(Step::Failed(f)); }
+    if opts.delete_source && audio != mp3 && mp3.exists() {
+        fs::remove_file(&mp3).await?;
+        let _ = fs::remove_file(done_marker(&mp3)).await;
+    }
+    let file = audio.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let bytes = fs::metadata(&audio).await.map(|m| m.len()).unwrap_or(0);
+    // a kept file keeps its old hash while its size still matches; the
+    // streamed hash is the mp3's, not a converted copy's
+    let sha256 = match (hash.filter(|_| audio == mp3), job.old.get(&file)) {
+        (Some(h), _) => h,
+        (None, Some(e)) if e.bytes == bytes => e.sha256.clone(),
+        (None, _) => {
+            let path = audio.clone();
+            tokio::task::spawn_blocking(move || sha256_file(&path)).await??
+        }
+    };
+    let entry = ManifestEntry { verse_key: v.verse_key.clone(), ayah, file, url, bytes, sha256, segments: pairs.len() };
+    let done = job.done.fetch_add(1, Ordering::Relaxed) + 1;
+    emit(&opts.progress, Event::AyahDone { ayah, stem, done, total: job.total });
+    Ok(Step::Done(seg_issues, entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{MockServer, Reply, TempDir, ThrottledServer};
+
+    // a HEAD answer for the CDN's untouched copy
+    fn cdn_size(len: u64) -> Reply {
+        Reply::ok("audio/mpeg", Vec::new()).header("Content-Length", len)
+    }
+
+    // a loudnorm'd collection file: smaller than the CDN's copy and without
+    // our tags, so only its done marker says it's whole
+    #[tokio::test]
+    async fn normalized_collection_file_is_not_truncated() {
+        let dir = TempDir::new();
+        let mp3 = dir.file("078_001.mp3", b"normalized audio");
+        let srv = MockServer::start(vec![cdn_size(5005), cdn_size(5005), cdn_size(5005)]);
+        let client = reqwest::Client::new();
+        let url = format!("{}/78_1.mp3", srv.url);
+
+        assert!(is_truncated(&client, &url, &mp3, None).await, "no marker: short means cut off");
+        std::fs::write(done_marker(&mp3), sha256_file(&mp3).unwrap()).unwrap();
+        assert!(!is_truncated(&client, &url, &mp3, None).await);
+        // the marker is for other bytes than the ones on disk now
+        std::fs::write(&mp3, b"something else").unwrap();
+        assert!(is_truncated(&client, &url, &mp3, None).await);
+    }
+
+    #[tokio::test]
+    async fn manifest_entry_vouches_for_a_rewritten_file() {
+        let dir = TempDir::new();
+        let mp3 = dir.file("001.mp3", b"normalized audio");
+        let srv = MockServer::start(vec![cdn_size(5005)]);
+        let entry = ManifestEntry {
+            verse_key: "1:1".into(), ayah: 1, file: "001.mp3".into(), url: String::new(),
+            bytes: 16, sha256: sha256_file(&mp3).unwrap(), segments: 0,
+        };
+        let url = format!("{}/1.mp3", srv.url);
+        assert!(!is_truncated(&reqwest::Client::new(), &url, &mp3, Some(&entry)).await);
+    }
+
+    #[test]
+    fn audio_urls_resolve_against_the_cdn() {
+        let cdn = &crate::api::endpoints().cdn;
+        // absolute: untouched
+        assert_eq!(resolve_audio_url("https://example.org/a/001.mp3"), "https://example.org/a/001.mp3");
+        assert_eq!(resolve_audio_url("http://example.org/001.mp3"), "http://example.org/001.mp3");
+        // protocol-relative: https on that host
+        assert_eq!(resolve_audio_url("//mirrors.quranicaudio.com/x/001.mp3"), "https://mirrors.quranicaudio.com/x/001.mp3");
+        // a host without a scheme
+        assert_eq!(resolve_audio_url("mirrors.quranicaudio.com/x/001.mp3"), "https://mirrors.quranicaudio.com/x/001.mp3");
+        // relative paths, with or without the leading slash, live on the CDN
+        assert_eq!(resolve_audio_url("Sudais/mp3/001001.mp3"), format!("{cdn}/Sudais/mp3/001001.mp3"));
+        assert_eq!(resolve_audio_url("/Sudais/mp3/001001.mp3"), format!("{cdn}/Sudais/mp3/001001.mp3"));
+        // a dotted folder isn't a host when the label isn't a TLD
+        assert_eq!(resolve_audio_url("v1.2/001.mp3"), format!("{cdn}/v1.2/001.mp3"));
+    }
+
+    // a CDN error page served as 200 must not end up as an ayah
+    #[tokio::test]
+    async fn html_instead_of_audio_is_rejected() {
+        let dir = TempDir::new();
+        let mp3 = dir.path().join("001.mp3");
+        let srv = MockServer::start(vec![
+            Reply::ok("text/html; charset=utf-8", "<html>Access denied</html>"),
+            Reply::ok("application/json", r#"{"error": "not found"}"#),
+            Reply::ok("audio/mpeg", b"ID3 audio".to_vec()),
+        ]);
+        let client = reqwest::Client::new();
+        let url = format!("{}/1.mp3", srv.url);
+        let opts = FilterOpts::default();
+        for ct in ["text/html", "application/json"] {
+            let err = fetch_to(&client, &url, &mp3, &opts, false, None).await.unwrap_err();
+            assert!(err.to_string().contains(ct), "{err}");
+            assert!(!mp3.exists() && !part_path(&mp3).exists());
+        }
+        let hash = fetch_to(&client, &url, &mp3, &opts, false, None).await.unwrap();
+        assert_eq!(std::fs::read(&mp3).unwrap(), b"ID3 audio");
+        assert_eq!(hash, sha256_file(&mp3).unwrap());
+    }
+
+    // more than two at once get a 429; the limit has to come down from eight
+    // and every file still arrive
+    #[tokio::test]
+    async fn rate_limits_bring_the_jobs_down() {
+        let dir = TempDir::new();
+        let srv = ThrottledServer::start(2);
+        let jobs = std::sync::Arc::new(AdaptiveJobs::new(1, 8));
+        jobs.start_at(8);
+        let opts = FilterOpts {
+            retry: RetryConfig { max_retries: 20, base_backoff: std::time::Duration::from_millis(5), ..Default::default() },
+            adaptive_jobs: Some(jobs.clone()),
+            ..Default::default()
+        };
+        let client = reqwest::Client::new();
+        let fetched: Vec<_> = futures_util::stream::iter(1..=24)
+            .map(|n| {
+                let (jobs, opts, client) = (&jobs, &opts, &client);
+                let (url, mp3) = (format!("{}/{n}.mp3", srv.url), dir.path().join(format!("{n:03}.mp3")));
+                async move {
+                    let _slot = jobs.acquire().await;
+                    fetch_to(client, &url, &mp3, opts, false, None).await
+                }
+            })
+            .buffer_unordered(8)
+            .collect()
+            .await;
+        for f in &fetched { if let Err(e) = f { panic!("{e:#}"); } }
+        assert!(srv.throttled.load(Ordering::SeqCst) > 0);
+        assert!(jobs.limit() < 8, "limit stayed at {}", jobs.limit());
+    }
+
+    // a dry run writes nothing, its plan included: that goes to the sink
+    #[tokio::test]
+    async fn a_dry_run_reports_its_plan_as_events() {
+        let dir = TempDir::new();
+        dir.file("001_002.mp3", b"ID3 already here");
+        let verses: Vec<String> = (1..=2)
+            .map(|a| format!(r#"{{"id": {a}, "verse_number": {a}, "verse_key": "1:{a}", "audio": {{"url": "x/{a}.mp3"}}}}"#))
+            .collect();
+        let srv = MockServer::start(vec![Reply::json(&format!(r#"{{"verses": [{}], "pagination": {{"next_page": null}}}}"#, verses.join(",")))]);
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let opts = FilterOpts {
+            dry_run: true,
+            progress: Some(Sink::new({ let seen = seen.clone(); move |e| seen.lock().unwrap().push(e) })),
+            ..Default::default()
+        };
+        let url = format!("{}/verses/by_juz/30", srv.url);
+        run_source(&reqwest::Client::new(), 7, Source::Collection(&url), &dir.path().to_string_lossy(), &opts).await.unwrap();
+        let seen = seen.lock().unwrap();
+        let plan: Vec<(String, bool)> = seen.iter()
+            .filter_map(|e| match e {
+                Event::Planned { path, exists, .. } => Some((path.file_name()?.to_string_lossy().to_string(), *exists)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(plan, [("001_001.mp3".to_string(), false), ("001_002.mp3".to_string(), true)]);
+        assert!(matches!(seen.last(), Some(Event::PlanTotal { get: 1, skip: 1 })), "{seen:?}");
+    }
+
+    fn failed_entry(dir: &TempDir, url: &str) -> FailedFetch {
+        FailedFetch {
+            verse_key: "1:1".into(), reciter: 7, url: url.into(), path: dir.path().join("001.mp3"),
+            error: "earlier failure".into(),
+            tags: Some(TagInfo { surah: "Al-Fatihah".into(), reciter: "Mishari".into() }), normalize: None,
+        }
+    }
+
+    // e.g. a --force re-download that failed: the old mp3 is still good
+    #[tokio::test]
+    async fn a_failed_retry_leaves_the_existing_file_alone() {
+        let dir = TempDir::new();
+        let old = dir.file("001.mp3", b"ID3 the old, good audio");
+        let srv = MockServer::start(vec![Reply::status(500)]);
+        let retry_file = dir.path().join("retry.json");
+        write_retry_file(&retry_file, &[failed_entry(&dir, &format!("{}/1.mp3", srv.url))]).unwrap();
+        let opts = FilterOpts { retry: RetryConfig { max_retries: 0, ..Default::default() }, ..Default::default() };
+        let (fixed, left) = retry_failed(&reqwest::Client::new(), &retry_file, &opts).await.unwrap();
+        assert_eq!((fixed, left), (0, 1));
+        assert_eq!(std::fs::read(&old).unwrap(), b"ID3 the old, good audio");
+        assert!(!part_path(&old).exists());
+        assert!(read_retry_file(&retry_file).unwrap()[0].error.contains("500"));
+    }
+
+    // a retried ayah ends up like any other download: tagged, marked done
+    // and listed in manifest.json
+    #[tokio::test]
+    async fn a_retried_file_is_finished_like_a_download() {
+        let dir = TempDir::new();
+        let mut audio = vec![0xFF, 0xFB, 0x90, 0x64];
+        audio.extend([0u8; 60]);
+        let srv = MockServer::start(vec![Reply::ok("audio/mpeg", audio)]);
+        let url = format!("{}/1.mp3", srv.url);
+        let retry_file = dir.path().join("retry.json");
+        write_retry_file(&retry_file, &[failed_entry(&dir, &url)]).unwrap();
+        let (fixed, left) = retry_failed(&reqwest::Client::new(), &retry_file, &FilterOpts::default()).await.unwrap();
+        assert_eq!((fixed, left), (1, 0));
+        assert!(!retry_file.exists());
+        let mp3 = dir.path().join("001.mp3");
+        assert!(has_our_tags(&mp3));
+        let hash = sha256_file(&mp3).unwrap();
+        assert_eq!(std::fs::read_to_string(done_marker(&mp3)).unwrap(), hash);
+        let manifest = read_manifest(dir.path()).unwrap();
+        assert_eq!(manifest.reciter_id, 7);
+        let [entry] = manifest.ayahs.as_slice() else { panic!("{:?}", manifest.ayahs) };
+        assert_eq!((entry.file.as_str(), entry.ayah, entry.url.as_str(), entry.sha256.as_str()), ("001.mp3", 1, url.as_str(), hash.as_str()));
     }
-    eprintln!();
-    Ok(())
 }
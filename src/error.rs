@@ -0,0 +1,54 @@
+use thiserror::Error;
+
+/// What can go wrong in the library, for callers that want to react to a
+/// particular failure (say, retry only `Http`). Anything without its own
+/// variant is `Other`, with the original message and context intact.
+#[derive(Debug, Error)]
+pub enum HifzrError {
+    #[error("unknown chapter: {0}")]
+    ChapterNotFound(String),
+    #[error("unknown reciter: {0}")]
+    ReciterNotFound(String),
+    #[error("HTTP {status} for {url}")]
+    Http { url: String, status: u16 },
+    #[error("bad verses spec: {0}")]
+    VerseSpec(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("ffmpeg not found; install it or point --ffmpeg / HIFZR_FFMPEG at it (`hifzr doctor` checks)")]
+    FfmpegMissing,
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+pub type Result<T, E = HifzrError> = std::result::Result<T, E>;
+
+// An anyhow error that already carries a HifzrError (a Http from deeper down
+// under some context) comes back as that variant
+impl From<anyhow::Error> for HifzrError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<HifzrError>() {
+            Ok(h) => h,
+            Err(e) => HifzrError::Other(e),
+        }
+    }
+}
+
+impl HifzrError {
+    /// Back to anyhow without the extra layer, so `downcast_ref` still finds
+    /// what `Other` wraps (`RateLimited`, `DeadlineHit`, `SomeFailed`)
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            HifzrError::Other(e) => e,
+            e => e.into(),
+        }
+    }
+
+    // Spawning ffmpeg failed: not installed, or something else
+    pub(crate) fn ffmpeg_spawn(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => HifzrError::FfmpegMissing,
+            _ => HifzrError::Io(e),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! What the library has to say while it works. Nothing in here draws
+//! anything: callers hand a [`Sink`] to the options they pass in and decide
+//! themselves how (and whether) to show each [`Event`].
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A line for the user as is ("segments: ok", "ok   2:255")
+    Note(String),
+    /// Something went wrong, but the work carries on
+    Warning(String),
+    /// A download pass starts on `total` ayahs
+    Started { total: usize },
+    /// `file` has `done` of `total` bytes (`total` unknown without a
+    /// Content-Length). Only sent with `FilterOpts::file_progress`.
+    FileBytes { file: String, done: u64, total: Option<u64> },
+    /// `file` is no longer in flight, fetched or not
+    FileDone { file: String },
+    /// One more ayah is on disk: `done` of the pass's `total`
+    AyahDone { ayah: u32, stem: String, done: usize, total: usize },
+    /// Sizing a source for `download --count`: `ayahs` so far
+    Sizing { source: String, ayahs: usize },
+    /// Every ayah of the pass (or of the sizing) is dealt with
+    Finished,
+    /// `--dry-run`: one target file and where it would come from
+    Planned { path: PathBuf, url: String, exists: bool },
+    /// `--dry-run`: that was all of them
+    PlanTotal { get: usize, skip: usize },
+}
+
+/// Where events go; cheap to clone, so every options struct can carry one
+#[derive(Clone)]
+pub struct Sink(Arc<dyn Fn(Event) + Send + Sync>);
+
+impl Sink {
+    pub fn new(f: impl Fn(Event) + Send + Sync + 'static) -> Sink {
+        Sink(Arc::new(f))
+    }
+
+    pub fn emit(&self, e: Event) {
+        (self.0)(e)
+    }
+}
+
+impl std::fmt::Debug for Sink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Sink")
+    }
+}
+
+// No sink means nobody is listening
+pub(crate) fn emit(sink: &Option<Sink>, e: Event) {
+    if let Some(s) = sink { s.emit(e); }
+}
@@ -0,0 +1,92 @@
+//! Finding ffmpeg and ffprobe, once per run. `--ffmpeg` (or `HIFZR_FFMPEG`)
+//! names the ffmpeg binary to use; ffprobe is looked for next to it, then on
+//! PATH.
+
+use serde::Serialize;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// A tool that answered `-version`
+#[derive(Debug, Clone, Serialize)]
+pub struct Tool {
+    pub path: PathBuf,
+    /// First line of its `-version` output
+    pub version: String,
+}
+
+static OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static FFMPEG: OnceLock<Option<Tool>> = OnceLock::new();
+static FFPROBE: OnceLock<Option<Tool>> = OnceLock::new();
+
+/// Use this ffmpeg instead of `$HIFZR_FFMPEG` or the one on PATH. Only works
+/// before the first use; false once the binary is already fixed.
+pub fn set_ffmpeg(path: PathBuf) -> bool {
+    OVERRIDE.set(path).is_ok() && FFMPEG.get().is_none()
+}
+
+// --ffmpeg, else $HIFZR_FFMPEG, else plain "ffmpeg" from PATH
+fn ffmpeg_path() -> PathBuf {
+    OVERRIDE.get().cloned()
+        .or_else(|| std::env::var_os("HIFZR_FFMPEG").filter(|v| !v.is_empty()).map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("ffmpeg"))
+}
+
+// ffprobe from the same folder as a configured ffmpeg, else from PATH
+fn ffprobe_path() -> PathBuf {
+    let ffmpeg = ffmpeg_path();
+    ffmpeg.parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .map(|d| d.join(format!("ffprobe{}", std::env::consts::EXE_SUFFIX)))
+        .filter(|p| p.is_file())
+        .unwrap_or_else(|| PathBuf::from("ffprobe"))
+}
+
+// Where a bare name like "ffmpeg" resolves to on PATH, for reporting
+fn on_path(path: PathBuf) -> PathBuf {
+    if path.components().count() != 1 { return path; }
+    let name = format!("{}{}", path.display(), std::env::consts::EXE_SUFFIX);
+    std::env::var_os("PATH")
+        .and_then(|paths| std::env::split_paths(&paths).map(|d| d.join(&name)).find(|p| p.is_file()))
+        .unwrap_or(path)
+}
+
+fn detect(path: PathBuf) -> Option<Tool> {
+    let out = Command::new(&path).arg("-version").output().ok()?;
+    if !out.status.success() { return None; }
+    let version = String::from_utf8_lossy(&out.stdout).lines().next().unwrap_or("").trim().to_string();
+    Some(Tool { path: on_path(path), version })
+}
+
+/// The ffmpeg in use, or None when it can't be run (checked once)
+pub fn detect_ffmpeg() -> Option<&'static Tool> {
+    FFMPEG.get_or_init(|| detect(ffmpeg_path())).as_ref()
+}
+
+/// The ffprobe in use, or None when it can't be run (checked once)
+pub fn detect_ffprobe() -> Option<&'static Tool> {
+    FFPROBE.get_or_init(|| detect(ffprobe_path())).as_ref()
+}
+
+/// mp3 encoding for every re-encode (--normalize, --concat). The LAME info
+/// frame at the start records the encoder delay and padding, which gapless
+/// players (mpv, foobar2000, Rockbox, iTunes) trim so joins don't click.
+pub const MP3_GAPLESS: &[&str] = &["-c:a", "libmp3lame", "-q:a", "2", "-write_xing", "1"];
+
+/// A command running the configured ffmpeg
+pub fn ffmpeg() -> Command {
+    Command::new(ffmpeg_path())
+}
+
+/// A command running the ffprobe that goes with it
+pub fn ffprobe() -> Command {
+    Command::new(ffprobe_path())
+}
+
+/// The single hint about a missing ffmpeg; later calls stay quiet
+pub fn warn_missing(what: &str, progress: &Option<crate::event::Sink>) {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| crate::event::emit(progress, crate::event::Event::Warning(format!(
+        "ffmpeg not found, so {what} won't run; install ffmpeg or point --ffmpeg / HIFZR_FFMPEG at it (`hifzr doctor` checks)"
+    ))));
+}
@@ -1,112 +1,1246 @@
 use anyhow::{Result};
-use std::{fs::File, io::Write, path::{Path, PathBuf}, process::Command};
+use crate::error::HifzrError;
+use crate::event::{emit, Event, Sink};
+use serde::Serialize;
+use std::{fs::File, io::Write, path::{Path, PathBuf}};
 
 
 fn base_dir(root: &str) -> PathBuf {
     Path::new(root).to_path_buf()
 }
 
-// Detect available ayahs by scanning *.mp3 in the chapter dir
-fn detect_available_ayahs(dir: &Path) -> Result<Vec<u32>> {
+/// How ayah files are named, e.g. "{ayah:03}" (the default, 001.mp3),
+/// "{surah}_{ayah:03}" or "{verse_key}" (2_255.mp3; the colon isn't safe
+/// everywhere). ":0N" pads a number to N digits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<NamePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NamePart {
+    Lit(String),
+    Surah(usize),
+    Ayah(usize),
+    VerseKey,
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        NameTemplate { parts: vec![NamePart::Ayah(3)] }
+    }
+}
+
+impl std::str::FromStr for NameTemplate {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(i) = rest.find('{') {
+            if i > 0 { parts.push(NamePart::Lit(rest[..i].to_string())); }
+            let j = rest[i..].find('}').ok_or_else(|| format!("unclosed {{ in {s:?}"))?;
+            let field = &rest[i + 1..i + j];
+            let (name, width) = match field.split_once(':') {
+                Some((n, w)) => (n, w.parse::<usize>().map_err(|_| format!("bad width in {{{field}}}"))?),
+                None => (field, 0),
+            };
+            parts.push(match name {
+                "surah" => NamePart::Surah(width),
+                "ayah" => NamePart::Ayah(width),
+                "verse_key" => NamePart::VerseKey,
+                other => return Err(format!("unknown field {{{other}}} (use surah, ayah or verse_key)")),
+            });
+            rest = &rest[i + j + 1..];
+        }
+        if !rest.is_empty() { parts.push(NamePart::Lit(rest.to_string())); }
+        if parts.iter().any(|p| matches!(p, NamePart::Lit(l) if l.contains(['/', '\\', '}']))) {
+            return Err(format!("{s:?} must be a plain file name"));
+        }
+        if !parts.iter().any(|p| matches!(p, NamePart::Ayah(_) | NamePart::VerseKey)) {
+            return Err(format!("{s:?} needs {{ayah}} or {{verse_key}} so every ayah gets its own file"));
+        }
+        Ok(NameTemplate { parts })
+    }
+}
+
+impl std::fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for p in &self.parts {
+            match p {
+                NamePart::Lit(l) => write!(f, "{l}")?,
+                NamePart::Surah(0) => write!(f, "{{surah}}")?,
+                NamePart::Surah(w) => write!(f, "{{surah:0{w}}}")?,
+                NamePart::Ayah(0) => write!(f, "{{ayah}}")?,
+                NamePart::Ayah(w) => write!(f, "{{ayah:0{w}}}")?,
+                NamePart::VerseKey => write!(f, "{{verse_key}}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl NameTemplate {
+    /// Whether the name depends on the surah number
+    pub fn uses_surah(&self) -> bool {
+        self.parts.iter().any(|p| matches!(p, NamePart::Surah(_) | NamePart::VerseKey))
+    }
+
+    /// The file stem for one ayah (no extension)
+    pub fn render(&self, surah: u32, ayah: u32) -> String {
+        self.parts.iter().map(|p| match p {
+            NamePart::Lit(l) => l.clone(),
+            NamePart::Surah(w) => format!("{:0w$}", surah, w = *w),
+            NamePart::Ayah(w) => format!("{:0w$}", ayah, w = *w),
+            NamePart::VerseKey => format!("{surah}_{ayah}"),
+        }).collect()
+    }
+
+    /// The number a stem written by `render` stands for (ayah, or the
+    /// `shift`ed file number); None for anything else in the folder
+    pub fn parse_ayah(&self, stem: &str) -> Option<u32> {
+        let digits = |s: &str, max: usize| s.bytes().take(max).take_while(u8::is_ascii_digit).count();
+        let mut rest = stem;
+        let mut ayah = None;
+        for (i, p) in self.parts.iter().enumerate() {
+            // two numbers back to back only split by width
+            let next_is_number = matches!(self.parts.get(i + 1), Some(NamePart::Surah(_) | NamePart::Ayah(_) | NamePart::VerseKey));
+            match p {
+                NamePart::Lit(l) => rest = rest.strip_prefix(l.as_str())?,
+                NamePart::Surah(w) | NamePart::Ayah(w) => {
+                    let n = digits(rest, if next_is_number && *w > 0 { *w } else { usize::MAX });
+                    if n == 0 { return None; }
+                    let value: u32 = rest[..n].parse().ok()?;
+                    if matches!(p, NamePart::Ayah(_)) { ayah = Some(value); }
+                    rest = &rest[n..];
+                }
+                NamePart::VerseKey => {
+                    let n = digits(rest, usize::MAX);
+                    rest = rest.get(n..)?.strip_prefix('_').filter(|_| n > 0)?;
+                    let m = digits(rest, usize::MAX);
+                    if m == 0 { return None; }
+                    ayah = Some(rest[..m].parse().ok()?);
+                    rest = &rest[m..];
+                }
+            }
+        }
+        rest.is_empty().then_some(ayah).flatten()
+    }
+}
+
+static NAME_TEMPLATE: std::sync::OnceLock<NameTemplate> = std::sync::OnceLock::new();
+
+/// Use `t` for every ayah file this process writes or looks for. Only the
+/// first call counts; false if the template was already fixed.
+pub fn set_name_template(t: NameTemplate) -> bool {
+    NAME_TEMPLATE.set(t).is_ok()
+}
+
+pub fn name_template() -> &'static NameTemplate {
+    NAME_TEMPLATE.get_or_init(NameTemplate::default)
+}
+
+// The surah number of a "<slug>-NNN/<reciter>" folder, for templates that
+// put it in the name
+fn surah_of_dir(dir: &Path) -> Option<u32> {
+    let surah = dir.parent()?.file_name()?.to_str()?;
+    surah.rsplit_once('-')?.1.parse().ok()
+}
+
+/// Audio files an ayah can be stored as, the `--convert` targets ahead of the
+/// mp3 they were made from
+pub const AUDIO_EXTS: [&str; 4] = ["opus", "ogg", "m4a", "mp3"];
+
+// Rank of an audio file's extension in AUDIO_EXTS; None for anything else
+fn audio_rank(p: &Path) -> Option<usize> {
+    let ext = p.extension()?.to_str()?;
+    AUDIO_EXTS.iter().position(|x| *x == ext)
+}
+
+// Detect available ayahs by scanning the audio files in the chapter dir
+pub fn detect_available_ayahs(dir: &Path) -> Result<Vec<u32>> {
+    detect_ayahs_shifted(dir, 0)
+}
+
+// Same, for folders from other tools whose file numbers are off by `shift`
+// (ayah = file number + shift; 0-based files use shift 1)
+pub fn detect_ayahs_shifted(dir: &Path, shift: i32) -> Result<Vec<u32>> {
+    let t = name_template();
     let mut v = Vec::new();
     for e in std::fs::read_dir(dir)? {
         let p = e?.path();
-        if p.extension().and_then(|s| s.to_str()) != Some("mp3") { continue; }
-        if let Some(stem) = p.file_stem().and_then(|s| s.to_str()) {
-            if let Ok(n) = stem.parse::<u32>() { v.push(n); }
-        }
+        if audio_rank(&p).is_none() { continue; }
+        if let Some(stem) = p.file_stem().and_then(|s| s.to_str())
+            && let Some(n) = ayah_of_stem(t, stem)
+            && let Some(a) = n.checked_add_signed(shift).filter(|&a| a > 0) { v.push(a); }
     }
     v.sort_unstable();
     v.dedup();
     Ok(v)
 }
 
+// The ayah number in a file stem: the template's, else the trailing number
+// ("1", "al-baqarah_001"), for folders named another way. Hidden files such
+// as the `.silence_500ms` gaps never count.
+fn ayah_of_stem(t: &NameTemplate, stem: &str) -> Option<u32> {
+    if stem.starts_with('.') { return None; }
+    t.parse_ayah(stem).or_else(|| {
+        let head = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+        stem[head.len()..].parse().ok()
+    })
+}
+
+// Whether the ayah's segments.json sidecar has at least one timing
+pub fn has_segments(dir: &Path, ayah: u32, shift: i32) -> bool {
+    std::fs::read(ayah_mp3(dir, ayah, shift).with_extension("segments.json")).ok()
+        .and_then(|b| serde_json::from_slice::<Vec<crate::models::Segment>>(&b).ok())
+        .is_some_and(|v| !v.is_empty())
+}
+
+// The audio file holding `ayah` under the same numbering and the name
+// template, a converted copy before the mp3; the .mp3 name when there's none
+pub fn ayah_mp3(dir: &Path, ayah: u32, shift: i32) -> PathBuf {
+    let n = ayah.saturating_add_signed(-shift);
+    let t = name_template();
+    let stem = t.render(surah_of_dir(dir).unwrap_or(0), n);
+    if let Some(p) = AUDIO_EXTS.iter().map(|x| dir.join(format!("{stem}.{x}"))).find(|p| p.is_file()) {
+        return p;
+    }
+    // not named by the template (or not a surah folder it can render for):
+    // whichever file carries this ayah's number
+    std::fs::read_dir(dir).into_iter().flatten().flatten()
+        .map(|e| e.path())
+        .filter(|p| p.file_stem().and_then(|s| s.to_str()).and_then(|s| ayah_of_stem(t, s)) == Some(n))
+        .filter_map(|p| audio_rank(&p).map(|r| (r, p)))
+        .min_by_key(|(r, _)| *r)
+        .map_or_else(|| dir.join(format!("{stem}.mp3")), |(_, p)| p)
+}
+
 
-// Parse "1-5,7,10-12" → sorted unique list
-pub fn parse_verses_spec(spec: &str) -> Result<Vec<u32>> {
+// Parse "1-5,7,10-12" → sorted unique list. "5-" runs to `max` (the surah's
+// last ayah), "-5" starts at 1; "12-10" or a "rev:" prefix keep the list in
+// the order asked for, for drilling backwards.
+pub fn parse_verses_spec(spec: &str, max: Option<u32>) -> crate::error::Result<Vec<u32>> {
+    let bad = |part: &str, why: &dyn std::fmt::Display| HifzrError::VerseSpec(format!("\"{part}\": {why}"));
+    // "rev:SPEC" plays the whole selection backwards
+    let (spec, rev) = match spec.trim().strip_prefix("rev:") {
+        Some(rest) => (rest, true),
+        None => (spec, false),
+    };
     let mut out = Vec::new();
+    let mut descending = false;
     for part in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
         if let Some((a,b)) = part.split_once('-') {
-            let a: u32 = a.parse()?;
-            let b: u32 = b.parse()?;
-            if a > b { anyhow::bail!("bad range: {part}"); }
-            out.extend(a..=b);
+            let a: u32 = match a.trim() { "" => 1, a => a.parse().map_err(|e| bad(part, &e))? };
+            let b: u32 = match (b.trim(), max) {
+                ("", Some(max)) => max,
+                ("", None) => return Err(bad(part, &"can't tell where it ends: the surah's ayah count is unknown")),
+                (b, _) => b.parse().map_err(|e| bad(part, &e))?,
+            };
+            // "12-10" drills backwards
+            if a > b {
+                descending = true;
+                out.extend((b..=a).rev());
+            } else {
+                out.extend(a..=b);
+            }
         } else {
-            out.push(part.parse()?);
+            out.push(part.parse().map_err(|e| bad(part, &e))?);
         }
     }
-    out.sort_unstable();
-    out.dedup();
+    if descending || rev {
+        // keep the asked-for order, first occurrence wins
+        let mut seen = std::collections::HashSet::new();
+        out.retain(|a| seen.insert(*a));
+    } else {
+        out.sort_unstable();
+        out.dedup();
+    }
+    if rev { out.reverse(); }
     Ok(out)
 }
 
-fn ensure_silence_mp3(out_root: &Path, gap_ms: u32) -> Option<PathBuf> {
+// The inverse of parse_verses_spec: [1,2,3,5,7,8] → "1-3,5,7-8"
+pub fn format_verses_spec(ayahs: &[u32]) -> String {
+    let mut sorted = ayahs.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut parts: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < sorted.len() {
+        let start = sorted[i];
+        let mut end = start;
+        while i + 1 < sorted.len() && sorted[i + 1] == end + 1 {
+            i += 1;
+            end = sorted[i];
+        }
+        parts.push(if start == end { start.to_string() } else { format!("{}-{}", start, end) });
+        i += 1;
+    }
+    parts.join(",")
+}
+
+// format_verses_spec keeping the list's order: "rev:" for a backwards list,
+// else runs with the descending ones written high-low ("12-10,1-3"), which
+// parse_verses_spec plays as written
+pub fn format_verses_ordered(ayahs: &[u32]) -> String {
+    if ayahs.is_sorted() {
+        return format_verses_spec(ayahs);
+    }
+    if ayahs.iter().rev().is_sorted() {
+        return format!("rev:{}", format_verses_spec(ayahs));
+    }
+    let mut parts: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < ayahs.len() {
+        let start = ayahs[i];
+        let mut end = start;
+        if i + 1 < ayahs.len() && ayahs[i + 1].abs_diff(start) == 1 {
+            let step = ayahs[i + 1] as i64 - start as i64;
+            while i + 1 < ayahs.len() && ayahs[i + 1] as i64 - end as i64 == step {
+                i += 1;
+                end = ayahs[i];
+            }
+        }
+        parts.push(if start == end { start.to_string() } else { format!("{}-{}", start, end) });
+        i += 1;
+    }
+    parts.join(",")
+}
+
+/// What fills a gap: silence, or a soft sine tone at this frequency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GapSound {
+    #[default]
+    Silence,
+    Tone(u32),
+}
+
+// the name encodes the sound and its parameters so variants never collide
+pub fn gap_path(out_root: &Path, gap_ms: u32, sound: GapSound) -> PathBuf {
+    match sound {
+        GapSound::Silence => out_root.join(format!(".silence_{}ms.mp3", gap_ms)),
+        GapSound::Tone(hz) => out_root.join(format!(".tone_{}hz_{}ms.mp3", hz, gap_ms)),
+    }
+}
+
+pub fn ensure_gap_audio(out_root: &Path, gap_ms: u32, sound: GapSound, progress: &Option<Sink>) -> Option<PathBuf> {
     if gap_ms == 0 { return None; }
-    let name = format!(".silence_{}ms.mp3", gap_ms);
-    let path = out_root.join(name);
+    let path = gap_path(out_root, gap_ms, sound);
     if path.exists() { return Some(path); }
 
     // try to generate via ffmpeg
-    let dur = format!("{:.3}", gap_ms as f32 / 1000.0);
-    let status = Command::new("ffmpeg")
-        .args([
-            "-hide_banner","-loglevel","error",
-            "-f","lavfi","-i","anullsrc=r=48000:cl=mono",
-            "-t",&dur,
-            "-c:a","libmp3lame","-q:a","9",
-        ])
-        .arg(&path)
-        .status();
+    let secs = gap_ms as f32 / 1000.0;
+    let dur = format!("{:.3}", secs);
+    let (source, filter) = match sound {
+        GapSound::Silence => ("anullsrc=r=48000:cl=mono".to_string(), None),
+        GapSound::Tone(hz) => {
+            // quiet, with short fades so it doesn't click
+            let fade = (secs / 4.0).min(0.05);
+            (
+                format!("sine=frequency={}:sample_rate=48000", hz),
+                Some(format!("volume=0.25,afade=t=in:d={fade:.3},afade=t=out:st={:.3}:d={fade:.3}", secs - fade)),
+            )
+        }
+    };
+    if crate::ffmpeg::detect_ffmpeg().is_some() {
+        let mut cmd = crate::ffmpeg::ffmpeg();
+        cmd.args(["-hide_banner","-loglevel","error", "-f","lavfi","-i"])
+            .arg(&source)
+            .args(["-t", &dur]);
+        if let Some(f) = &filter {
+            cmd.args(["-af", f]);
+        }
+        let status = cmd
+            .args(["-ac","1", "-c:a","libmp3lame","-q:a","9"])
+            .arg(&path)
+            .status();
 
-    match status {
-        Ok(s) if s.success() => Some(path),
-        _ => None,
+        if status.is_ok_and(|s| s.success()) {
+            return Some(path);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // no ffmpeg: silence can still be written by hand; a tone can't
+    let silent = gap_path(out_root, gap_ms, GapSound::Silence);
+    if let GapSound::Tone(_) = sound {
+        crate::ffmpeg::warn_missing("--gap-sound tone (gaps are silent instead)", progress);
+        if silent.exists() { return Some(silent); }
+    }
+    match write_silent_mp3(&silent, gap_ms) {
+        Ok(()) => Some(silent),
+        Err(e) => {
+            emit(progress, Event::Warning(format!("couldn't create {} ({}); gaps are disabled", silent.display(), e)));
+            None
+        }
     }
 }
 
-pub fn build_ayah_playlist(
+// MPEG-1 Layer III, 32 kbps, 48 kHz, mono, no CRC: 96-byte frames of 1152
+// samples (24 ms). All-zero side info and main data decode to silence.
+fn write_silent_mp3(path: &Path, gap_ms: u32) -> std::io::Result<()> {
+    const FRAME_LEN: usize = 96;
+    let mut frame = [0u8; FRAME_LEN];
+    frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x14, 0xC0]);
+    let frames = gap_ms.div_ceil(24) as usize;
+    std::fs::write(path, frame.repeat(frames))
+}
+
+// Gap after an ayah as a fraction of its probed length, rounded to 100ms
+// so the silence cache stays small
+fn ratio_gap_ms(mp3: &Path, ratio: f32) -> Option<u32> {
+    let dur = crate::probe::duration_ms(mp3)?;
+    let ms = (dur as f32 * ratio / 100.0).round() as u32 * 100;
+    Some(ms.max(100))
+}
+
+/// Rank ayahs by probed length and keep the top N
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Select { Longest(usize), Shortest(usize) }
+
+impl std::str::FromStr for Select {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (kind, n) = s.split_once(':')
+            .ok_or_else(|| format!("expected longest:N or shortest:N, got {s:?}"))?;
+        let n: usize = n.trim().parse().map_err(|_| format!("bad count in {s:?}"))?;
+        if n == 0 { return Err("count must be at least 1".into()); }
+        match kind.trim() {
+            "longest" => Ok(Select::Longest(n)),
+            "shortest" => Ok(Select::Shortest(n)),
+            other => Err(format!("unknown selection {other:?} (use longest or shortest)")),
+        }
+    }
+}
+
+impl std::fmt::Display for Select {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Select::Longest(n) => write!(f, "longest:{n}"),
+            Select::Shortest(n) => write!(f, "shortest:{n}"),
+        }
+    }
+}
+
+/// `--repeat`: a flat "5", or per-ayah counts like "1:10,2-4:3,*:5" where
+/// `*` is the count for every ayah not named
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepeatSpec {
+    pub default: Option<usize>,
+    pub per_ayah: std::collections::BTreeMap<u32, usize>,
+}
+
+impl std::str::FromStr for RepeatSpec {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let count = |n: &str| match n.trim().parse::<usize>() {
+            Ok(0) => Err("repeat count must be at least 1".to_string()),
+            Ok(n) => Ok(n),
+            Err(_) => Err(format!("bad repeat count {:?}", n.trim())),
+        };
+        let mut spec = RepeatSpec::default();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.rsplit_once(':') {
+                None => spec.default = Some(count(part)?),
+                Some(("*", n)) => spec.default = Some(count(n)?),
+                // the ayah side is a --verses spec, so "2-4:3" works too
+                Some((ayahs, n)) => {
+                    let n = count(n)?;
+                    for a in parse_verses_spec(ayahs, None).map_err(|e| e.to_string())? {
+                        spec.per_ayah.insert(a, n);
+                    }
+                }
+            }
+        }
+        if spec.default.is_none() && spec.per_ayah.is_empty() {
+            return Err("empty --repeat".into());
+        }
+        Ok(spec)
+    }
+}
+
+// Probe every candidate (spec or folder scan) and return the picked
+// (ayah, duration_ms) pairs in rank order
+pub fn select_by_duration(out_root: &str, verses: Option<&str>, sel: Select, shift: i32, max: Option<u32>) -> Result<Vec<(u32, u32)>> {
+    let dir = base_dir(out_root);
+    let list = match verses {
+        Some(spec) => parse_verses_spec(spec, max)?,
+        None => detect_ayahs_shifted(&dir, shift)?,
+    };
+    let mut ranked: Vec<(u32, u32)> = list.into_iter()
+        .filter_map(|a| crate::probe::duration_ms(&ayah_mp3(&dir, a, shift)).map(|ms| (a, ms)))
+        .collect();
+    if ranked.is_empty() {
+        anyhow::bail!("no ayah durations available (is ffprobe installed and are the files downloaded?)");
+    }
+    let n = match sel {
+        Select::Longest(n) => { ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0))); n }
+        Select::Shortest(n) => { ranked.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0))); n }
+    };
+    ranked.truncate(n);
+    Ok(ranked)
+}
+
+// Split a --verses spec into the ayahs on disk and the ones that aren't
+pub fn split_existing(out_root: &str, spec: &str, shift: i32, max: Option<u32>) -> Result<(Vec<u32>, Vec<u32>)> {
+    let dir = base_dir(out_root);
+    let have: std::collections::HashSet<u32> = if dir.is_dir() {
+        detect_ayahs_shifted(&dir, shift)?.into_iter().collect()
+    } else {
+        Default::default()
+    };
+    Ok(parse_verses_spec(spec, max)?.into_iter().partition(|a| have.contains(a)))
+}
+
+// missing.txt next to the playlist, one `NNN.mp3` per line (named like the
+// folder's other files, `shift` included), so whoever gets the playlist knows
+// what to download. A stale list is removed when nothing is missing.
+pub fn write_missing_list(out_root: &str, missing: &[u32], shift: i32) -> Result<Option<PathBuf>> {
+    let path = base_dir(out_root).join("missing.txt");
+    if missing.is_empty() {
+        if path.exists() { std::fs::remove_file(&path)?; }
+        return Ok(None);
+    }
+    let dir = base_dir(out_root);
+    let text: String = missing.iter()
+        .filter_map(|&a| ayah_mp3(&dir, a, shift).file_name().map(|n| format!("{}\n", n.to_string_lossy())))
+        .collect();
+    std::fs::write(&path, text)?;
+    Ok(Some(path))
+}
+
+// A paper tracker: a title line, then one "[ ] 2:255" per ayah
+pub fn write_checklist(path: &Path, title: &str, chapter: u32, ayahs: &[u32]) -> Result<()> {
+    let mut text = format!("{title}\n\n");
+    for a in ayahs {
+        text.push_str(&format!("[ ] {}:{}\n", chapter, a));
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// Playlist order of the chosen ayahs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    Ayah,
+    DurationAsc,
+    DurationDesc,
+}
+
+// Rearrange `list` by probed length for a warmup ramp (or the reverse).
+// Ayahs that can't be probed keep ayah order at the end.
+pub fn order_ayahs(dir: &Path, list: Vec<u32>, order: Order, shift: i32) -> Vec<(u32, Option<u32>)> {
+    let mut v: Vec<(u32, Option<u32>)> = list.into_iter()
+        .map(|a| {
+            let ms = if order == Order::Ayah { None } else { crate::probe::duration_ms(&ayah_mp3(dir, a, shift)) };
+            (a, ms)
+        })
+        .collect();
+    match order {
+        Order::Ayah => {}
+        Order::DurationAsc => v.sort_by_key(|&(a, ms)| (ms.is_none(), ms, a)),
+        Order::DurationDesc => v.sort_by_key(|&(a, ms)| (ms.is_none(), std::cmp::Reverse(ms), a)),
+    }
+    v
+}
+
+/// Random play order, to test recall without the sequence as a crutch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shuffle {
+    pub seed: u64,
+    /// Scatter the takes too, instead of keeping each ayah's repeats together
+    pub repeats: bool,
+}
+
+/// Rewrite entries under `root` to `prefix/<relative path>` (e.g. for a phone)
+#[derive(Debug, Clone)]
+pub struct PathPrefix {
+    pub root: PathBuf,
+    pub prefix: String,
+}
+
+impl PathPrefix {
+    fn apply(&self, p: &Path) -> String {
+        match p.strip_prefix(&self.root) {
+            Ok(rel) => {
+                let rel: Vec<_> = rel.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+                format!("{}/{}", self.prefix.trim_end_matches('/'), rel.join("/"))
+            }
+            Err(_) => p.display().to_string(),
+        }
+    }
+}
+
+/// Playlist file type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    /// bare #EXTM3U + paths
+    #[default]
+    M3u,
+    /// [playlist] with FileN/TitleN/LengthN, for older players
+    Pls,
+    /// .m3u8 with an #EXTINF length and title before every entry
+    ExtM3u,
+}
+
+impl PlaylistFormat {
+    fn ext(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "m3u",
+            PlaylistFormat::Pls => "pls",
+            PlaylistFormat::ExtM3u => "m3u8",
+        }
+    }
+}
+
+/// How each ayah is laid out in the playlist
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistOpts {
+    pub repeat: usize,
+    /// Repeat counts that differ from `repeat`, by ayah
+    pub repeat_for: std::collections::BTreeMap<u32, usize>,
+    /// Build up: ayah 1, then 1-2, then 1-3, ... (each pass with its repeats)
+    pub cumulative: bool,
+    pub gap_ms: u32,                      // between repeats of one ayah
+    pub between_ayah_gap_ms: Option<u32>, // between different ayahs; None = gap_ms
+    pub gap_ratio: Option<f32>,  // gap = ratio × ayah length; falls back to gap_ms
+    pub path_prefix: Option<PathPrefix>,
+    pub gap_sound: GapSound,
+    pub order: Order,
+    /// Leave gap files out of the m3u; the gap only shows in #EXTINF lengths
+    pub omit_gap_files: bool,
+    /// Write the m3u (and latest_playlist.txt) here instead of next to the audio
+    pub playlist_dir: Option<PathBuf>,
+    /// ayah = file number + stem_shift, for folders not numbered 001.mp3 = ayah 1
+    pub stem_shift: i32,
+    /// Leave out ayahs whose segments.json sidecar is missing or empty
+    pub require_segments: bool,
+    pub format: PlaylistFormat,
+    /// The surah's ayah count, for open-ended --verses like "5-"
+    pub ayah_count: Option<u32>,
+    /// A second reciter's folder: each ayah's takes from it follow the first's
+    pub interleave: Option<PathBuf>,
+    /// Repeats for the --interleave reciter; None = the same as the first
+    pub repeat_b: Option<usize>,
+    pub shuffle: Option<Shuffle>,
+    /// Told about ayahs left out and gaps or cue sheets that couldn't be made
+    pub progress: Option<Sink>,
+}
+
+impl PlaylistOpts {
+    pub fn repeats(&self, ayah: u32) -> usize {
+        self.repeat_for.get(&ayah).copied().unwrap_or(self.repeat)
+    }
+
+    /// Takes of the ayah from the --interleave reciter (0 without one)
+    pub fn repeats_b(&self, ayah: u32) -> usize {
+        match self.interleave {
+            Some(_) => self.repeat_b.unwrap_or_else(|| self.repeats(ayah)),
+            None => 0,
+        }
+    }
+
+    /// Ayah plays the playlist comes to for `ayahs`, in play order
+    pub fn plays(&self, ayahs: &[u32]) -> usize {
+        let n = ayahs.len();
+        ayahs.iter().enumerate()
+            // in cumulative mode the i-th ayah is in every pass from the i-th on
+            .map(|(i, &a)| (self.repeats(a) + self.repeats_b(a)) * if self.cumulative { n - i } else { 1 })
+            .sum()
+    }
+}
+
+// Where the playlist for the audio in `dir` goes. In a shared --playlist-dir
+// the name carries the surah and reciter folders so playlists don't collide.
+pub fn playlist_path(dir: &Path, opts: &PlaylistOpts) -> PathBuf {
+    match &opts.playlist_dir {
+        None => dir.join(format!("hifz_ayah.{}", opts.format.ext())), // simple stable name
+        Some(pd) => {
+            let name = |p: Option<&Path>| p.and_then(|p| p.file_name()).map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            pd.join(format!("{}_{}.{}", name(dir.parent()), name(Some(dir)), opts.format.ext()))
+        }
+    }
+}
+
+// pointer for quick playback scripts / waybar
+fn write_latest_pointer(m3u: &Path) -> Result<()> {
+    let dir = m3u.parent().unwrap_or(Path::new("."));
+    std::fs::write(dir.join("latest_playlist.txt"), m3u.to_string_lossy().as_bytes())?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind { Ayah, Gap, Missing }
+
+/// One line the playlist would contain (missing ayahs are listed but never written)
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistEntry {
+    pub kind: EntryKind,
+    pub ayah: u32,
+    pub take: Option<usize>,     // 1-based repeat index for ayah lines
+    pub gap_ms: Option<u32>,     // silence length for gap lines
+    pub path: PathBuf,
+    pub exists: bool,
+}
+
+// --require-segments: drop the ayahs without timings, saying how many went
+fn keep_segmented(dir: &Path, list: Vec<u32>, opts: &PlaylistOpts) -> Vec<u32> {
+    let before = list.len();
+    let kept: Vec<u32> = list.into_iter().filter(|&a| has_segments(dir, a, opts.stem_shift)).collect();
+    if kept.len() < before {
+        emit(&opts.progress, Event::Note(format!("--require-segments: left out {} ayah(s) without segment timings", before - kept.len())));
+    }
+    kept
+}
+
+// Work out the ordered entries. With `dry_run` the silence files are only
+// named, not generated, so nothing touches the disk.
+pub fn plan_ayah_playlist(
     out_root: &str,
-    _chapter: u32,               // kept for filename consistency if you want
     verses: Option<&str>,
-    repeat: usize,
-    gap_ms: u32,
-) -> Result<PathBuf> {
+    opts: &PlaylistOpts,
+    dry_run: bool,
+) -> Result<Vec<PlaylistEntry>> {
     let dir = base_dir(out_root);
+    use rand::{SeedableRng, seq::SliceRandom};
     let list = match verses {
-        Some(spec) => parse_verses_spec(spec)?,
-        None => detect_available_ayahs(&dir)?,
+        Some(spec) => parse_verses_spec(spec, opts.ayah_count)?,
+        None if dry_run && !dir.is_dir() => Vec::new(),
+        None => detect_ayahs_shifted(&dir, opts.stem_shift)?,
+    };
+    let list: Vec<u32> = match opts.order {
+        Order::Ayah => list,
+        order => order_ayahs(&dir, list, order, opts.stem_shift).into_iter().map(|(a, _)| a).collect(),
     };
+    let mut list = if opts.require_segments { keep_segmented(&dir, list, opts) } else { list };
+    let mut rng = opts.shuffle.map(|s| rand::rngs::StdRng::seed_from_u64(s.seed));
+    if let Some(rng) = rng.as_mut() {
+        list.shuffle(rng);
+    }
+    let scatter = opts.shuffle.is_some_and(|s| s.repeats);
 
-    let silence = ensure_silence_mp3(&dir, gap_ms);
+    let silence_for = |ms: u32| -> Option<(u32, PathBuf)> {
+        if dry_run || opts.omit_gap_files {
+            (ms > 0).then(|| (ms, gap_path(&dir, ms, opts.gap_sound)))
+        } else {
+            ensure_gap_audio(&dir, ms, opts.gap_sound, &opts.progress).map(|p| (ms, p))
+        }
+    };
+    let fixed_silence = silence_for(opts.gap_ms);
+    // an explicit between-ayah gap wins over both --gap-ms and --gap-ratio
+    let between_silence = opts.between_ayah_gap_ms.map(silence_for);
 
-    let m3u = dir.join("hifz_ayah.m3u"); // simple stable name
-    let mut f = File::create(&m3u)?;
-    writeln!(f, "#EXTM3U")?;
+    // an ayah's takes from one file, the repeat gap between them and the
+    // between-ayah gap after the last
+    let takes = |ayah: u32, mp3: PathBuf, repeat: usize| {
+        let silence = match opts.gap_ratio.and_then(|r| ratio_gap_ms(&mp3, r)) {
+            Some(ms) => silence_for(ms),
+            None => fixed_silence.clone(),
+        };
+        let between = between_silence.clone().unwrap_or_else(|| silence.clone());
+        let gap = |(ms, p): &(u32, PathBuf)| PlaylistEntry {
+            kind: EntryKind::Gap, ayah, take: None, gap_ms: Some(*ms), exists: p.exists(), path: p.clone(),
+        };
+        let mut block = Vec::new();
+        for r in 0..repeat {
+            block.push(PlaylistEntry { kind: EntryKind::Ayah, ayah, take: Some(r + 1), gap_ms: None, path: mp3.clone(), exists: true });
+            // the repeat gap goes between takes, not after the last one
+            if let Some(s) = silence.as_ref()
+                && r + 1 < repeat { block.push(gap(s)); }
+        }
+        // gap between ayahs
+        if let Some(s) = between.as_ref() {
+            block.push(gap(s));
+        }
+        block
+    };
 
+    // one block per ayah: its takes and gaps, then the --interleave
+    // reciter's; a file that isn't there is a lone Missing entry instead
+    let mut blocks: Vec<Vec<PlaylistEntry>> = Vec::new();
     for ayah in list {
-        let mp3 = dir.join(format!("{:03}.mp3", ayah));
-        if !mp3.exists() {
-            eprintln!("skip {:03}: missing {}", ayah, mp3.display());
+        let mut block = Vec::new();
+        let sources = std::iter::once((dir.as_path(), opts.repeats(ayah)))
+            .chain(opts.interleave.as_deref().map(|d| (d, opts.repeats_b(ayah))));
+        for (d, repeat) in sources {
+            let mp3 = ayah_mp3(d, ayah, opts.stem_shift);
+            if !mp3.exists() {
+                block.push(PlaylistEntry { kind: EntryKind::Missing, ayah, take: None, gap_ms: None, path: mp3, exists: false });
+            } else if scatter {
+                // --shuffle-repeats: every take is its own block, followed
+                // by the between-ayah gap since its neighbours are other ayahs
+                blocks.extend(std::iter::repeat_with(|| takes(ayah, mp3.clone(), 1)).take(repeat));
+            } else {
+                block.extend(takes(ayah, mp3, repeat));
+            }
+        }
+        if !block.is_empty() { blocks.push(block); }
+    }
+    if scatter && let Some(rng) = rng.as_mut() {
+        blocks.shuffle(rng);
+        // number the takes in the order they now play
+        let mut seen: std::collections::HashMap<PathBuf, usize> = Default::default();
+        for e in blocks.iter_mut().flatten().filter(|e| e.kind == EntryKind::Ayah) {
+            let n = seen.entry(e.path.clone()).or_default();
+            *n += 1;
+            e.take = Some(*n);
+        }
+    }
+    if !opts.cumulative {
+        return Ok(blocks.into_iter().flatten().collect());
+    }
+    // a pass per ayah that's there; a missing file is listed once and adds
+    // nothing to the passes
+    let mut out = Vec::new();
+    let mut passes: Vec<Vec<PlaylistEntry>> = Vec::new();
+    for block in blocks {
+        let (missing, playable): (Vec<_>, Vec<_>) = block.into_iter().partition(|e| e.kind == EntryKind::Missing);
+        out.extend(missing);
+        if playable.is_empty() { continue; }
+        passes.push(playable);
+        out.extend(passes.iter().flatten().cloned());
+    }
+    Ok(out)
+}
+
+// One playable entry, whatever the file format
+struct Line {
+    path: String,
+    title: String,
+    secs: i64, // -1 when unknown
+}
+
+fn playlist_lines(entries: &[PlaylistEntry], opts: &PlaylistOpts, surah: &str) -> Result<Vec<Line>> {
+    let timed = opts.omit_gap_files || opts.format != PlaylistFormat::M3u;
+    let mut out = Vec::new();
+    for (i, e) in entries.iter().enumerate() {
+        if e.kind == EntryKind::Missing {
+            emit(&opts.progress, Event::Note(format!("skip {:03}: missing {}", e.ayah, e.path.display())));
             continue;
         }
-        for r in 0..repeat {
-            writeln!(f, "{}", mp3.display())?;
-            // insert silence between repeats (and between ayahs) except after the last repeat
-            if let Some(s) = silence.as_ref() {
-                if (gap_ms).abs_diff(0) > 500 {
-                    if r + 1 < repeat { writeln!(f, "{}", s.display())?; }
-                }
+        if opts.omit_gap_files && e.kind == EntryKind::Gap { continue; }
+        let secs = if !timed {
+            -1
+        } else if e.kind == EntryKind::Gap {
+            (e.gap_ms.unwrap_or(0) as f64 / 1000.0).round() as i64
+        } else {
+            // without gap files, the pause meant to follow shows in the length
+            let gap = entries.get(i + 1)
+                .filter(|n| opts.omit_gap_files && n.kind == EntryKind::Gap)
+                .and_then(|n| n.gap_ms)
+                .unwrap_or(0);
+            crate::probe::duration_ms(&e.path)
+                .map(|ms| ((ms + gap) as f64 / 1000.0).round() as i64)
+                .unwrap_or(-1)
+        };
+        let title = match e.kind {
+            EntryKind::Gap => format!("{} pause", surah),
+            _ => format!("{} ayah {} (take {})", surah, e.ayah, e.take.unwrap_or(1)),
+        };
+        let path = match &opts.path_prefix {
+            Some(pp) => pp.apply(&e.path),
+            // a playlist away from the audio needs paths that resolve from anywhere
+            None if opts.playlist_dir.is_some() => std::path::absolute(&e.path)?.display().to_string(),
+            None => e.path.display().to_string(),
+        };
+        out.push(Line { path, title, secs });
+    }
+    Ok(out)
+}
+
+// The body of an m3u/m3u8: the part appended to an existing one as well
+fn write_m3u_lines(f: &mut File, lines: &[Line], opts: &PlaylistOpts) -> Result<()> {
+    let extinf = opts.omit_gap_files || opts.format == PlaylistFormat::ExtM3u;
+    for l in lines {
+        if extinf { writeln!(f, "#EXTINF:{},{}", l.secs, l.title)?; }
+        writeln!(f, "{}", l.path)?;
+    }
+    Ok(())
+}
+
+fn write_playlist(m3u: &Path, lines: &[Line], opts: &PlaylistOpts) -> Result<()> {
+    let mut f = File::create(m3u)?;
+    match opts.format {
+        PlaylistFormat::Pls => {
+            writeln!(f, "[playlist]")?;
+            for (i, l) in lines.iter().enumerate() {
+                writeln!(f, "File{}={}", i + 1, l.path)?;
+                writeln!(f, "Title{}={}", i + 1, l.title)?;
+                writeln!(f, "Length{}={}", i + 1, l.secs)?;
             }
+            writeln!(f, "NumberOfEntries={}", lines.len())?;
+            writeln!(f, "Version=2")?;
         }
-        if let Some(s) = silence.as_ref() {
-            // gap between ayahs
-            if (gap_ms).abs_diff(0) > 500 {
-                writeln!(f, "{}", s.display())?;
-            }
+        PlaylistFormat::M3u | PlaylistFormat::ExtM3u => {
+            writeln!(f, "#EXTM3U")?;
+            write_m3u_lines(&mut f, lines, opts)?;
         }
     }
+    Ok(())
+}
 
-    // pointer for quick playback scripts / waybar
-    std::fs::write(dir.join("latest_playlist.txt"), m3u.to_string_lossy().as_bytes())?;
+pub fn build_ayah_playlist(
+    out_root: &str,
+    surah: &str,                 // name for #EXTINF/pls titles
+    verses: Option<&str>,
+    opts: &PlaylistOpts,
+) -> Result<PathBuf> {
+    let dir = base_dir(out_root);
+    let entries = plan_ayah_playlist(out_root, verses, opts, false)?;
+
+    let m3u = playlist_path(&dir, opts);
+    if let Some(pd) = &opts.playlist_dir { std::fs::create_dir_all(pd)?; }
+    write_playlist(&m3u, &playlist_lines(&entries, opts, surah)?, opts)?;
+
+    write_latest_pointer(&m3u)?;
+    Ok(m3u)
+}
+
+// One playlist spanning several folders (e.g. the surahs of a juz), written
+// into `target`. Each part is (surah/reciter folder, verses spec or None for
+// whatever the folder has, surah name), in play order.
+pub fn build_combined_playlist(target: &Path, parts: &[(String, Option<String>, String)], opts: &PlaylistOpts) -> Result<PathBuf> {
+    let m3u = playlist_path(target, opts);
+    std::fs::create_dir_all(m3u.parent().unwrap_or(target))?;
+    let mut lines = Vec::new();
+    for (root, spec, surah) in parts {
+        let entries = plan_ayah_playlist(root, spec.as_deref(), opts, false)?;
+        lines.extend(playlist_lines(&entries, opts, surah)?);
+    }
+    write_playlist(&m3u, &lines, opts)?;
+    write_latest_pointer(&m3u)?;
     Ok(m3u)
 }
+
+// Bake the playlist (repeats and gap files included) into one
+// `surah_hifz.mp3` next to the audio with ffmpeg's concat demuxer. Stream
+// copy when every piece has the same sample rate/channels, else re-encode.
+// `gapless` always re-encodes: a copy keeps every piece's encoder padding,
+// which plays as a tiny pause at each join.
+pub fn concat_playlist(out_root: &str, verses: Option<&str>, opts: &PlaylistOpts, gapless: bool) -> Result<PathBuf> {
+    if crate::ffmpeg::detect_ffmpeg().is_none() { return Err(HifzrError::FfmpegMissing.into()); }
+    let dir = base_dir(out_root);
+    let entries = plan_ayah_playlist(out_root, verses, opts, false)?;
+    let played: Vec<&PlaylistEntry> = entries.iter()
+        .filter(|e| e.kind != EntryKind::Missing && e.exists)
+        .collect();
+    let files: Vec<&Path> = played.iter().map(|e| e.path.as_path()).collect();
+    if files.is_empty() {
+        anyhow::bail!("nothing to concatenate in {}", dir.display());
+    }
+
+    let list = dir.join(".concat_list.txt");
+    let mut text = String::new();
+    for f in &files {
+        // concat list quoting: ' closes, \' is a literal quote, ' reopens
+        let abs = std::path::absolute(f)?;
+        text.push_str(&format!("file '{}'\n", abs.to_string_lossy().replace('\'', "'\\''")));
+    }
+    std::fs::write(&list, text)?;
+
+    let mut formats = std::collections::HashSet::new();
+    let mut lengths = std::collections::HashMap::new();
+    for f in files.iter().collect::<std::collections::BTreeSet<_>>() {
+        let info = crate::probe::probe(f).ok();
+        lengths.insert(*f, info.as_ref().and_then(|i| i.duration_ms));
+        formats.insert(info.map(|i| (i.sample_rate, i.channels)));
+    }
+    let out = dir.join("surah_hifz.mp3");
+    let part = dir.join("surah_hifz.mp3.part");
+    let run = |codec: &[&str]| {
+        crate::ffmpeg::ffmpeg()
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "concat", "-safe", "0", "-i"])
+            .arg(&list)
+            .args(codec)
+            .args(["-f", "mp3"])
+            .arg(&part)
+            .status()
+            .map(|s| s.success())
+            .map_err(HifzrError::ffmpeg_spawn)
+    };
+    let joined = (|| {
+        let copied = !gapless && formats.len() == 1 && run(&["-c", "copy"])?;
+        Ok::<_, HifzrError>(copied || {
+            if !gapless { emit(&opts.progress, Event::Note("stream copy not possible; re-encoding (slower)".into())); }
+            run(crate::ffmpeg::MP3_GAPLESS)?
+        })
+    })();
+    let _ = std::fs::remove_file(&list);
+    let ok = joined?;
+    if !ok {
+        let _ = std::fs::remove_file(&part);
+        anyhow::bail!("ffmpeg couldn't join the ayahs (is ffmpeg installed?)");
+    }
+    std::fs::rename(&part, &out)?;
+    let cue = dir.join("surah_hifz.cue");
+    match ayah_starts(&played, &lengths) {
+        Some(starts) => std::fs::write(&cue, cue_sheet("surah_hifz.mp3", &starts))?,
+        None => {
+            emit(&opts.progress, Event::Note("no surah_hifz.cue: ffprobe couldn't time every piece".into()));
+            let _ = std::fs::remove_file(&cue);
+        }
+    }
+    Ok(out)
+}
+
+// Where each ayah is first heard in the joined file, from the pieces'
+// lengths (a gap ffprobe can't time counts as its --gap-ms)
+fn ayah_starts(played: &[&PlaylistEntry], lengths: &std::collections::HashMap<&Path, Option<u32>>) -> Option<Vec<(u32, u64)>> {
+    let mut at = 0u64;
+    let mut seen = std::collections::HashSet::new();
+    let mut starts = Vec::new();
+    for e in played {
+        if e.kind == EntryKind::Ayah && seen.insert(e.ayah) { starts.push((e.ayah, at)); }
+        let ms = lengths.get(e.path.as_path()).copied().flatten().or(e.gap_ms)?;
+        at += u64::from(ms);
+    }
+    Some(starts)
+}
+
+// Cue sheet times are MM:SS:FF, with 75 frames to the second
+fn cue_time(ms: u64) -> String {
+    let frames = ms * 75 / 1000;
+    format!("{:02}:{:02}:{:02}", frames / 75 / 60, frames / 75 % 60, frames % 75)
+}
+
+// One TRACK per ayah, for players that navigate a single file by cue sheet
+fn cue_sheet(file: &str, starts: &[(u32, u64)]) -> String {
+    let mut out = format!("FILE \"{file}\" MP3\n");
+    for (i, (ayah, ms)) in starts.iter().enumerate() {
+        out += &format!("  TRACK {:02} AUDIO\n    TITLE \"Ayah {ayah}\"\n    INDEX 01 {}\n", i + 1, cue_time(*ms));
+    }
+    out
+}
+
+// Ayahs an existing playlist already plays, read back from the audio file names
+fn playlist_ayahs(m3u: &Path, shift: i32) -> Result<std::collections::HashSet<u32>> {
+    let text = std::fs::read_to_string(m3u)?;
+    let t = name_template();
+    Ok(text.lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.rsplit(['/', '\\']).next())
+        .map(Path::new)
+        .filter(|name| audio_rank(name).is_some())
+        .filter_map(|name| name.file_stem()?.to_str())
+        .filter_map(|stem| ayah_of_stem(t, stem))
+        .filter_map(|n| n.checked_add_signed(shift))
+        .collect())
+}
+
+// Grow the existing playlist with ayahs that became available since it was
+// written (the playlist itself is the previous snapshot). Builds from scratch
+// when there is no playlist yet. Returns the playlist and the ayahs added.
+pub fn append_new_ayahs(
+    out_root: &str,
+    surah: &str,
+    verses: Option<&str>,
+    opts: &PlaylistOpts,
+) -> Result<(PathBuf, Vec<u32>)> {
+    let dir = base_dir(out_root);
+    let m3u = playlist_path(&dir, opts);
+    let candidates = match verses {
+        Some(spec) => parse_verses_spec(spec, opts.ayah_count)?,
+        None => detect_ayahs_shifted(&dir, opts.stem_shift)?,
+    };
+    if !m3u.exists() {
+        let m3u = build_ayah_playlist(out_root, surah, verses, opts)?;
+        let added = candidates.into_iter()
+            .filter(|&a| ayah_mp3(&dir, a, opts.stem_shift).exists())
+            .filter(|&a| !opts.require_segments || has_segments(&dir, a, opts.stem_shift))
+            .collect();
+        return Ok((m3u, added));
+    }
+
+    let have = playlist_ayahs(&m3u, opts.stem_shift)?;
+    let added: Vec<u32> = candidates.into_iter()
+        .filter(|&a| !have.contains(&a) && ayah_mp3(&dir, a, opts.stem_shift).exists())
+        .filter(|&a| !opts.require_segments || has_segments(&dir, a, opts.stem_shift))
+        .collect();
+    if !added.is_empty() {
+        let spec = added.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+        let entries = plan_ayah_playlist(out_root, Some(&spec), opts, false)?;
+        let mut f = std::fs::OpenOptions::new().append(true).open(&m3u)?;
+        write_m3u_lines(&mut f, &playlist_lines(&entries, opts, surah)?, opts)?;
+    }
+
+    write_latest_pointer(&m3u)?;
+    Ok((m3u, added))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    // a folder with 001.mp3.. for `ayahs`, contents irrelevant
+    fn surah_dir(ayahs: std::ops::RangeInclusive<u32>) -> TempDir {
+        let dir = TempDir::new();
+        for a in ayahs {
+            dir.file(&format!("{a:03}.mp3"), b"ID3");
+        }
+        dir
+    }
+
+    fn gaps_of(entries: &[PlaylistEntry], ms: u32) -> usize {
+        entries.iter().filter(|e| e.kind == EntryKind::Gap && e.gap_ms == Some(ms)).count()
+    }
+
+    fn spec(s: &str, max: Option<u32>) -> Vec<u32> {
+        parse_verses_spec(s, max).unwrap()
+    }
+
+    #[test]
+    fn open_ended_ranges() {
+        assert_eq!(spec("5-", Some(7)), vec![5, 6, 7]);
+        assert_eq!(spec("-3", Some(7)), vec![1, 2, 3]);
+        assert_eq!(spec("-3", None), vec![1, 2, 3]);
+        // "5-" needs the surah's length
+        assert!(matches!(parse_verses_spec("5-", None), Err(HifzrError::VerseSpec(_))));
+    }
+
+    #[test]
+    fn reversed_ranges_keep_their_order() {
+        assert_eq!(spec("10-8", None), vec![10, 9, 8]);
+        // once any part runs backwards the whole spec plays as written
+        assert_eq!(spec("1-3,10-8", None), vec![1, 2, 3, 10, 9, 8]);
+        assert_eq!(spec("3-1,2", None), vec![3, 2, 1]);
+        assert_eq!(spec("rev:1-3,5", None), vec![5, 3, 2, 1]);
+        // forward specs are sorted and deduplicated
+        assert_eq!(spec("3,1-2,2", None), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn bad_specs_are_errors() {
+        for bad in ["x", "1-y", "1,,z"] {
+            assert!(matches!(parse_verses_spec(bad, Some(7)), Err(HifzrError::VerseSpec(_))), "{bad}");
+        }
+    }
+
+    #[test]
+    fn short_gaps_get_one_line_each() {
+        let dir = surah_dir(1..=3);
+        let root = dir.path().to_string_lossy().to_string();
+        let opts = PlaylistOpts { repeat: 3, gap_ms: 250, ..Default::default() };
+        let entries = plan_ayah_playlist(&root, Some("1-3"), &opts, true).unwrap();
+        // 3 takes with 2 repeat gaps in between, then the between-ayah gap
+        assert_eq!(entries.iter().filter(|e| e.kind == EntryKind::Ayah).count(), 9);
+        assert_eq!(gaps_of(&entries, 250), 9);
+        assert!(entries[1].path.ends_with(".silence_250ms.mp3"));
+
+        let opts = PlaylistOpts { between_ayah_gap_ms: Some(1000), ..opts };
+        let entries = plan_ayah_playlist(&root, Some("1-3"), &opts, true).unwrap();
+        assert_eq!(gaps_of(&entries, 250), 6);
+        assert_eq!(gaps_of(&entries, 1000), 3);
+        // a dry run only names the silence files
+        assert!(!dir.path().join(".silence_250ms.mp3").exists());
+    }
+
+    #[test]
+    fn formatted_specs_parse_back() {
+        for list in [vec![1, 2, 3, 5, 7, 8], vec![4], vec![1, 3, 5], vec![10, 11, 12, 20]] {
+            let text = format_verses_spec(&list);
+            assert_eq!(spec(&text, None), list, "{text}");
+        }
+        assert_eq!(format_verses_spec(&[1, 2, 3, 5, 7, 8]), "1-3,5,7-8");
+        // unsorted input comes out sorted
+        assert_eq!(format_verses_spec(&[3, 1, 2, 2]), "1-3");
+
+        // the ordered form keeps backwards and mixed orders
+        for list in [vec![1, 2, 3], vec![7, 6, 5, 3], vec![1, 2, 3, 10, 9, 8], vec![12, 11, 10, 1, 2]] {
+            let text = format_verses_ordered(&list);
+            assert_eq!(spec(&text, None), list, "{text}");
+        }
+        assert_eq!(format_verses_ordered(&[7, 6, 5, 3]), "rev:3,5-7");
+        assert_eq!(format_verses_ordered(&[1, 2, 3, 10, 9, 8]), "1-3,10-8");
+    }
+
+    #[test]
+    fn mixed_folders_count_only_ayah_files() {
+        let dir = TempDir::new();
+        for name in ["001.mp3", "2.mp3", "al-fatihah_003.mp3", "004.opus", "004.mp3",
+                     ".silence_500ms.mp3", ".tone_440hz_500ms.mp3", "005.segments.json", "notes.txt", "cover.jpg"] {
+            dir.file(name, b"");
+        }
+        assert_eq!(detect_available_ayahs(dir.path()).unwrap(), vec![1, 2, 3, 4]);
+        // a 000.mp3-based folder, read one up
+        assert_eq!(detect_ayahs_shifted(dir.path(), 1).unwrap(), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn stems_follow_the_template_then_the_trailing_number() {
+        let t: NameTemplate = "{surah:03}_{ayah:03}".parse().unwrap();
+        assert_eq!(ayah_of_stem(&t, "002_255"), Some(255));
+        assert_eq!(ayah_of_stem(&t, "255"), Some(255));
+        assert_eq!(ayah_of_stem(&t, "al-baqarah_007"), Some(7));
+        assert_eq!(ayah_of_stem(&t, ".silence_500ms"), None);
+        let t: NameTemplate = "{verse_key}".parse().unwrap();
+        assert_eq!(ayah_of_stem(&t, "2_255"), Some(255));
+        assert_eq!(t.render(2, 255), "2_255");
+        // back-to-back numbers split by the first one's width; unpadded
+        // they can't be told apart
+        let t: NameTemplate = "{surah:03}{ayah:03}".parse().unwrap();
+        assert_eq!(t.parse_ayah("002007"), Some(7));
+        let t: NameTemplate = "{surah}{ayah:03}".parse().unwrap();
+        assert_eq!(t.parse_ayah("2007"), None);
+        assert!("{surah}".parse::<NameTemplate>().is_err());
+    }
+
+    #[test]
+    fn missing_list_names_files_like_the_folder() {
+        // numbered from 000.mp3: ayah 3 would be 002.mp3
+        let dir = TempDir::new();
+        dir.file("000.mp3", b"");
+        let root = dir.path().to_string_lossy().to_string();
+        let (have, missing) = split_existing(&root, "1-3", 1, None).unwrap();
+        assert_eq!((have, missing.clone()), (vec![1], vec![2, 3]));
+        let path = write_missing_list(&root, &missing, 1).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "001.mp3\n002.mp3\n");
+        // nothing missing: the old list goes
+        assert_eq!(write_missing_list(&root, &[], 1).unwrap(), None);
+        assert!(!dir.path().join("missing.txt").exists());
+    }
+
+    #[test]
+    fn cue_times_are_minutes_seconds_frames() {
+        assert_eq!(cue_time(0), "00:00:00");
+        assert_eq!(cue_time(1_000), "00:01:00");
+        // 75 frames a second: half a second is frame 37
+        assert_eq!(cue_time(61_500), "01:01:37");
+        assert_eq!(cue_time(999), "00:00:74");
+        assert_eq!(cue_time(6_000_000), "100:00:00");
+    }
+
+    #[test]
+    fn cue_tracks_start_at_each_ayahs_first_take() {
+        let entry = |kind, ayah, path: &str, gap_ms| PlaylistEntry {
+            kind, ayah, take: None, gap_ms, path: PathBuf::from(path), exists: true,
+        };
+        let played = [
+            entry(EntryKind::Ayah, 1, "001.mp3", None),
+            entry(EntryKind::Gap, 1, "gap.mp3", Some(500)),
+            entry(EntryKind::Ayah, 1, "001.mp3", None),
+            entry(EntryKind::Gap, 1, "gap.mp3", Some(500)),
+            entry(EntryKind::Ayah, 2, "002.mp3", None),
+        ];
+        let played: Vec<&PlaylistEntry> = played.iter().collect();
+        // the gap file can't be timed, so its --gap-ms stands in
+        let lengths = std::collections::HashMap::from([
+            (Path::new("001.mp3"), Some(4_000)),
+            (Path::new("002.mp3"), Some(2_000)),
+            (Path::new("gap.mp3"), None),
+        ]);
+        let starts = ayah_starts(&played, &lengths).unwrap();
+        assert_eq!(starts, [(1, 0), (2, 9_000)]);
+        let cue = cue_sheet("surah_hifz.mp3", &starts);
+        assert_eq!(cue, "FILE \"surah_hifz.mp3\" MP3\n  TRACK 01 AUDIO\n    TITLE \"Ayah 1\"\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    TITLE \"Ayah 2\"\n    INDEX 01 00:09:00\n");
+        for line in cue.lines().filter(|l| l.trim_start().starts_with("INDEX")) {
+            let t: Vec<u64> = line.rsplit(' ').next().unwrap().split(':').map(|n| n.parse().unwrap()).collect();
+            assert!(t.len() == 3 && t[1] < 60 && t[2] < 75, "{line}");
+        }
+        // an untimed ayah means no cue sheet at all
+        let lengths = std::collections::HashMap::from([(Path::new("001.mp3"), Some(4_000))]);
+        assert_eq!(ayah_starts(&played, &lengths), None);
+    }
+}
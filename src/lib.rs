@@ -0,0 +1,19 @@
+//! hifzr as a library: the quran.com API client, downloads, playlist
+//! building and name lookups behind the `hifzr` binary. Nothing here parses
+//! arguments or prints anything (that stays in `main.rs`); progress and
+//! warnings are reported as [`event::Event`]s to the `Sink` a caller puts in
+//! the options it passes.
+
+pub mod models;
+pub mod api;
+pub mod download;
+pub mod error;
+pub mod event;
+pub mod ffmpeg;
+pub mod hifz;
+pub mod library;
+pub mod lookup;
+pub mod probe;
+
+#[cfg(test)]
+mod testutil;
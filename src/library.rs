@@ -0,0 +1,194 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::lookup::Chapter;
+
+/// One `<reciter-slug>/` leaf with the ayahs found in it
+#[derive(Debug, Clone, Serialize)]
+pub struct ReciterDir {
+    pub slug: String,
+    pub path: PathBuf,
+    pub ayahs: Vec<u32>,
+}
+
+/// One `<surah-slug>/` folder, matched back to its chapter when possible
+#[derive(Debug, Clone, Serialize)]
+pub struct SurahDir {
+    pub slug: String,
+    pub chapter: Option<u32>,
+    pub total: Option<u32>,      // known verse count for the chapter
+    pub reciters: Vec<ReciterDir>,
+}
+
+// chapter_slug() ends with "-NNN"
+pub fn chapter_id_from_slug(slug: &str) -> Option<u32> {
+    slug.rsplit_once('-')?.1.parse().ok()
+}
+
+fn subdirs(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut v = Vec::new();
+    for e in std::fs::read_dir(dir)? {
+        let p = e?.path();
+        if !p.is_dir() { continue; }
+        let Some(name) = p.file_name().and_then(|s| s.to_str()) else { continue };
+        if name.starts_with('.') { continue; }
+        v.push((name.to_string(), p));
+    }
+    v.sort();
+    Ok(v)
+}
+
+// Sibling reciter folders that hold ayahs and share a slug word (3+ chars)
+// with `dir`, best match first. Used to explain an empty folder after an
+// upstream reciter rename.
+pub fn similar_siblings(dir: &Path) -> Vec<(String, usize)> {
+    let (Some(parent), Some(name)) = (dir.parent(), dir.file_name().and_then(|s| s.to_str())) else {
+        return Vec::new();
+    };
+    let words: Vec<&str> = name.split('-').filter(|w| w.len() >= 3).collect();
+    let mut found: Vec<(usize, String, usize)> = subdirs(parent).unwrap_or_default()
+        .into_iter()
+        .filter(|(slug, _)| slug != name)
+        .filter_map(|(slug, path)| {
+            let shared = slug.split('-').filter(|w| words.contains(w)).count();
+            let n = crate::hifz::detect_available_ayahs(&path).map(|a| a.len()).unwrap_or(0);
+            (shared > 0 && n > 0).then_some((shared, slug, n))
+        })
+        .collect();
+    found.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    found.into_iter().map(|(_, slug, n)| (slug, n)).collect()
+}
+
+// Reciter folders under a surah folder that hold ayahs
+pub fn reciter_dirs_with_ayahs(surah_dir: &Path) -> Vec<(String, usize)> {
+    subdirs(surah_dir).unwrap_or_default()
+        .into_iter()
+        .filter_map(|(slug, path)| {
+            let n = crate::hifz::detect_available_ayahs(&path).map(|a| a.len()).unwrap_or(0);
+            (n > 0).then_some((slug, n))
+        })
+        .collect()
+}
+
+/// Something `hifzr clean` deletes, with the bytes it frees
+#[derive(Debug, Clone, Serialize)]
+pub struct Removal {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+// Everything hifz writes next to the audio: playlists, the latest_playlist.txt
+// pointer and the .silence_/.tone_ gap files
+fn is_playlist_file(name: &str) -> bool {
+    name == "latest_playlist.txt"
+        || name.starts_with(".silence_") || name.starts_with(".tone_")
+        || [".m3u", ".m3u8", ".pls"].iter().any(|x| name.ends_with(x))
+}
+
+fn tree_bytes(p: &Path) -> u64 {
+    match std::fs::symlink_metadata(p) {
+        Ok(m) if m.is_dir() => std::fs::read_dir(p).into_iter().flatten().flatten()
+            .map(|e| tree_bytes(&e.path())).sum(),
+        Ok(m) => m.len(),
+        Err(_) => 0,
+    }
+}
+
+// What cleaning `dir` (a surah or reciter folder) removes: the folder itself,
+// or with `playlists_only` just the playlist files in it and its subfolders
+pub fn clean_targets(dir: &Path, playlists_only: bool) -> Result<Vec<Removal>> {
+    if !dir.is_dir() { return Ok(Vec::new()); }
+    if !playlists_only {
+        return Ok(vec![Removal { path: dir.to_path_buf(), bytes: tree_bytes(dir) }]);
+    }
+    let mut dirs = vec![dir.to_path_buf()];
+    dirs.extend(subdirs(dir)?.into_iter().map(|(_, p)| p));
+    let mut out = Vec::new();
+    for d in dirs {
+        for e in std::fs::read_dir(&d)? {
+            let p = e?.path();
+            if !p.is_file() { continue; }
+            if p.file_name().and_then(|s| s.to_str()).is_some_and(is_playlist_file) {
+                out.push(Removal { bytes: tree_bytes(&p), path: p });
+            }
+        }
+    }
+    out.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(out)
+}
+
+// Delete the targets; the freed byte count
+pub fn remove(targets: &[Removal]) -> Result<u64> {
+    for t in targets {
+        if t.path.is_dir() { std::fs::remove_dir_all(&t.path)?; } else { std::fs::remove_file(&t.path)?; }
+    }
+    Ok(targets.iter().map(|t| t.bytes).sum())
+}
+
+// Walk `<root>/<surah-slug>/<reciter-slug>/` and count ayah files per leaf
+pub fn scan(root: &Path, chapters: &[Chapter]) -> Result<Vec<SurahDir>> {
+    let mut out = Vec::new();
+    for (slug, path) in subdirs(root)? {
+        let chapter = chapter_id_from_slug(&slug);
+        let total = chapter
+            .and_then(|id| chapters.iter().find(|c| c.id == id))
+            .and_then(|c| c.verses_count);
+        let mut reciters = Vec::new();
+        for (rslug, rpath) in subdirs(&path)? {
+            let ayahs = crate::hifz::detect_available_ayahs(&rpath)?;
+            reciters.push(ReciterDir { slug: rslug, path: rpath, ayahs });
+        }
+        out.push(SurahDir { slug, chapter, total, reciters });
+    }
+    out.sort_by_key(|s| s.chapter.unwrap_or(u32::MAX));
+    Ok(out)
+}
+
+// <root>/library.json: the whole scan plus counts, for apps that don't want
+// to walk the folders themselves
+pub fn write_index(root: &Path, chapters: &[Chapter]) -> Result<PathBuf> {
+    let surahs: Vec<serde_json::Value> = scan(root, chapters)?
+        .into_iter()
+        .map(|s| {
+            let reciters: Vec<serde_json::Value> = s.reciters.iter().map(|r| serde_json::json!({
+                "slug": r.slug,
+                "path": r.path,
+                "ayahs": r.ayahs,
+                "count": r.ayahs.len(),
+                "complete": s.total.map(|t| r.ayahs.len() as u32 >= t),
+            })).collect();
+            serde_json::json!({
+                "slug": s.slug,
+                "chapter": s.chapter,
+                "total": s.total,
+                "reciters": reciters,
+            })
+        })
+        .collect();
+    let index = serde_json::json!({
+        "root": root,
+        "generator": concat!("hifzr ", env!("CARGO_PKG_VERSION")),
+        "surahs": surahs,
+    });
+    let path = root.join("library.json");
+    std::fs::write(&path, serde_json::to_vec_pretty(&index)?)?;
+    Ok(path)
+}
+
+// <surah-dir>/info.txt so a browsed library explains itself. Written once;
+// `force` rewrites it. Returns the path when something was written.
+pub fn write_surah_info(surah_dir: &Path, c: &Chapter, reciter: &str, force: bool) -> Result<Option<PathBuf>> {
+    let path = surah_dir.join("info.txt");
+    if path.exists() && !force { return Ok(None); }
+    let mut text = format!("Surah {}: {}\n", c.id, c.name_complex);
+    let field = |label: &str, v: &str| if v.is_empty() { String::new() } else { format!("{label}: {v}\n") };
+    text += &field("Arabic", &c.name_arabic);
+    text += &field("Meaning", &c.translated_name.name);
+    if let Some(n) = c.verses_count { text += &format!("Ayahs: {n}\n"); }
+    text += &field("Revealed in", c.revelation_place.as_deref().unwrap_or_default());
+    text += &field("Reciter", reciter);
+    std::fs::create_dir_all(surah_dir)?;
+    std::fs::write(&path, text)?;
+    Ok(Some(path))
+}
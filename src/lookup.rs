@@ -1,6 +1,11 @@
 use anyhow::{Result};
+use crate::api::RetryConfig;
+use crate::error::HifzrError;
+use crate::event::{emit, Event};
 use reqwest::Client;
 use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::OnceCell;
 use unicode_normalization::UnicodeNormalization;
 
 /// Chapters (surahs)
@@ -10,12 +15,21 @@ pub struct Chapter {
     pub name_simple: String,         // canonical simple English name
     #[serde(default)]
     pub name_complex: String,        // nicer display form (from API)
-    // #[serde(default)]
-    // pub name_arabic: String,
+    #[serde(default)]
+    pub verses_count: Option<u32>,   // None when the API leaves it out
+    #[serde(default)]
+    pub translated_name: TranslatedName, // meaning, e.g. "The Cow"
+    #[serde(default)]
+    pub name_arabic: String,
+    #[serde(default)]
+    pub revelation_place: Option<String>, // "makkah" / "madinah"
 }
 
-#[derive(Debug, Deserialize)]
-struct ChaptersResp { }//chapters: Vec<Chapter> }
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TranslatedName {
+    #[serde(default)]
+    pub name: String,
+}
 
 /// Reciters list (for audio "recitation id")
 #[derive(Debug, Clone, Deserialize)]
@@ -27,10 +41,29 @@ pub struct Reciter {
     pub style: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct RecitersResp { }//recitations: Vec<Reciter> }
-// add at top
-// keep existing Reciter, RecitersResp, etc.
+/// Tafsirs (commentaries) the API offers, for `--tafsir ID`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tafsir {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub author_name: String,
+    #[serde(default)]
+    pub language_name: String,
+}
+
+/// Translations the API offers, for `--translation ID`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Translation {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub author_name: String,
+    #[serde(default)]
+    pub language_name: String,
+}
 
 pub fn norm_key(s: &str) -> String {
     s.nfkd().filter(|c| c.is_ascii()).collect::<String>()
@@ -40,6 +73,27 @@ pub fn norm_key(s: &str) -> String {
         .collect()
 }
 
+// Like norm_key but keeps word boundaries: "Sa`ud ash-Shuraym" → [saud, ash,
+// shuraym] (an apostrophe for the ayn stays inside its word, like a dropped
+// "ʿ" does). CamelCase words also yield their parts: "AbdulBaset" →
+// abdulbaset, abdul, baset.
+pub fn norm_words(s: &str) -> Vec<String> {
+    let ascii: String = s.nfkd().filter(|c| c.is_ascii() && !matches!(c, '`' | '\'')).collect();
+    let mut out = Vec::new();
+    for word in ascii.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()) {
+        out.push(word.to_ascii_lowercase());
+        let mut parts = vec![String::new()];
+        let mut prev_lower = false;
+        for ch in word.chars() {
+            if ch.is_ascii_uppercase() && prev_lower { parts.push(String::new()); }
+            prev_lower = ch.is_ascii_lowercase();
+            if let Some(p) = parts.last_mut() { p.push(ch.to_ascii_lowercase()); }
+        }
+        if parts.len() > 1 { out.extend(parts); }
+    }
+    out
+}
+
 pub fn slugify(s: &str) -> String {
     let mut out = String::new();
     let mut dash = false;
@@ -51,42 +105,350 @@ pub fn slugify(s: &str) -> String {
 }
 
 // canonical API fetches
-const BASE: &str = "https://api.quran.com/api/v4";
 
-pub async fn fetch_chapters(client: &Client) -> Result<Vec<Chapter>> {
+// Both lists are fetched at most once per process (failures aren't cached,
+// so a later call retries). Key these by language if that ever stops being "en".
+static CHAPTERS: OnceCell<Vec<Chapter>> = OnceCell::const_new();
+static RECITERS: OnceCell<Vec<Reciter>> = OnceCell::const_new();
+
+// The lists barely change, so they're also kept on disk for a week
+const CACHE_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 3600);
+static REFRESH: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Ignore (and overwrite) the on-disk cache for the rest of this run
+pub fn refresh_cache() {
+    REFRESH.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+// $XDG_CACHE_HOME/hifzr, else ~/.cache/hifzr
+fn cache_dir() -> Option<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()).map(std::path::PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))?;
+    Some(base.join("hifzr"))
+}
+
+// GET a list endpoint's body through the cache in `name`. A stale copy still
+// beats nothing when the network is down.
+async fn cached_body(client: &Client, url: &str, name: &str, retry: &RetryConfig) -> Result<String> {
+    let path = cache_dir().map(|d| d.join(name));
+    let cached = path.as_ref().and_then(|p| {
+        let age = std::fs::metadata(p).ok()?.modified().ok()?.elapsed().unwrap_or_default();
+        Some((std::fs::read_to_string(p).ok()?, age))
+    });
+    let refresh = REFRESH.load(std::sync::atomic::Ordering::Relaxed);
+    if !refresh
+        && let Some((body, age)) = &cached
+        && *age < CACHE_MAX_AGE {
+        return Ok(body.clone());
+    }
+    let fetched = async {
+        anyhow::Ok(crate::api::send_retrying(client.get(url), retry).await?.error_for_status()?.text().await?)
+    }.await;
+    match (fetched, cached) {
+        (Ok(body), _) => {
+            if let Some(p) = &path {
+                // best effort; a read-only home just means no cache
+                let _ = p.parent().map(std::fs::create_dir_all);
+                let _ = std::fs::write(p, &body);
+            }
+            Ok(body)
+        }
+        (Err(e), Some((body, _))) => {
+            emit(&retry.progress, Event::Warning(format!("{:#}; using the cached {}", e, name)));
+            Ok(body)
+        }
+        (Err(e), None) => Err(e),
+    }
+}
+
+pub async fn fetch_chapters(client: &Client, retry: &RetryConfig) -> Result<Vec<Chapter>> {
     #[derive(Deserialize)] struct R { chapters: Vec<Chapter> }
-    let url = format!("{BASE}/chapters?language=en");
-    Ok(client.get(url).send().await?.error_for_status()?.json::<R>().await?
-        .chapters)
+    let list = CHAPTERS.get_or_try_init(|| async {
+        let url = format!("{}/chapters?language=en", crate::api::endpoints().api);
+        let body = cached_body(client, &url, "chapters.json", retry).await?;
+        anyhow::Ok(serde_json::from_str::<R>(&body)?.chapters)
+    }).await?;
+    Ok(list.clone())
 }
 
-pub async fn fetch_reciters(client: &Client) -> Result<Vec<Reciter>> {
+pub async fn fetch_reciters(client: &Client, retry: &RetryConfig) -> Result<Vec<Reciter>> {
     #[derive(Deserialize)] struct R { recitations: Vec<Reciter> }
-    let url = format!("{BASE}/resources/recitations?language=en");
-    Ok(client.get(url).send().await?.error_for_status()?.json::<R>().await?
-        .recitations)
+    let list = RECITERS.get_or_try_init(|| async {
+        let url = format!("{}/resources/recitations?language=en", crate::api::endpoints().api);
+        let body = cached_body(client, &url, "reciters.json", retry).await?;
+        anyhow::Ok(serde_json::from_str::<R>(&body)?.recitations)
+    }).await?;
+    Ok(list.clone())
+}
+
+pub async fn fetch_tafsirs(client: &Client, retry: &RetryConfig) -> Result<Vec<Tafsir>> {
+    #[derive(Deserialize)] struct R { tafsirs: Vec<Tafsir> }
+    let url = format!("{}/resources/tafsirs?language=en", crate::api::endpoints().api);
+    let body = cached_body(client, &url, "tafsirs.json", retry).await?;
+    Ok(serde_json::from_str::<R>(&body)?.tafsirs)
+}
+
+pub async fn fetch_translations(client: &Client, retry: &RetryConfig) -> Result<Vec<Translation>> {
+    #[derive(Deserialize)] struct R { translations: Vec<Translation> }
+    let url = format!("{}/resources/translations?language=en", crate::api::endpoints().api);
+    let body = cached_body(client, &url, "translations.json", retry).await?;
+    Ok(serde_json::from_str::<R>(&body)?.translations)
 }
 
 // resolve using SERVER names (not your input)
-pub fn resolve_chapter<'a>(chapters: &'a [Chapter], spec: &str) -> Option<&'a Chapter> {
-    if let Ok(n) = spec.parse::<u32>() { return chapters.iter().find(|c| c.id == n); }
+pub fn resolve_chapter<'a>(chapters: &'a [Chapter], spec: &str) -> crate::error::Result<&'a Chapter> {
     let key = norm_key(spec);
-    chapters.iter().find(|c| norm_key(&c.name_simple) == key
-        || norm_key(&c.name_complex) == key)
+    let found = match spec.parse::<u32>() {
+        Ok(n) => chapters.iter().find(|c| c.id == n),
+        // norm_key drops Arabic script entirely; such a spec is compared as typed
+        Err(_) if key.is_empty() => chapters.iter().find(|c| !c.name_arabic.is_empty() && c.name_arabic == spec.trim()),
+        Err(_) => chapters.iter().find(|c| norm_key(&c.name_simple) == key
+            || norm_key(&c.name_complex) == key
+            || (!c.translated_name.name.is_empty() && norm_key(&c.translated_name.name) == key)),
+    };
+    found.ok_or_else(|| HifzrError::ChapterNotFound(spec.to_string()))
 }
 
-pub fn resolve_reciter<'a>(reciters: &'a [Reciter], spec: &str) -> Option<&'a Reciter> {
-    if let Ok(n) = spec.parse::<u32>() { return reciters.iter().find(|r| r.id == n); }
+// Closest chapter names by edit distance on the normalized key, for a
+// "did you mean" after resolve_chapter found nothing. Best first.
+pub fn suggest_chapters<'a>(chapters: &'a [Chapter], spec: &str, n: usize) -> Vec<&'a Chapter> {
     let key = norm_key(spec);
-    reciters.iter().find(|r| {
-        let n = norm_key(&r.reciter_name);
-        let s = r.style.as_deref().map(norm_key).unwrap_or_default();
-        n.contains(&key) || key.contains(&n) || (!s.is_empty() && s == key)
-    })
+    if key.is_empty() { return Vec::new(); }
+    let limit = (key.len() / 3).max(2);
+    let mut ranked: Vec<(usize, &Chapter)> = chapters.iter()
+        .filter_map(|c| {
+            [&c.name_simple, &c.name_complex, &c.translated_name.name].into_iter()
+                .filter(|s| !s.is_empty())
+                .flat_map(|s| {
+                    // also try without the article, so "baqara" is near "Al-Baqarah"
+                    let bare = s.split_once(['-', ' '])
+                        .filter(|(art, _)| art.len() <= 3 && art.to_ascii_lowercase().starts_with('a'))
+                        .map(|(_, rest)| norm_key(rest));
+                    std::iter::once(norm_key(s)).chain(bare)
+                })
+                .map(|s| strsim::levenshtein(&key, &s))
+                .min()
+                .filter(|&d| d <= limit)
+                .map(|d| (d, c))
+        })
+        .collect();
+    ranked.sort_by_key(|(d, c)| (*d, c.id));
+    ranked.into_iter().take(n).map(|(_, c)| c).collect()
+}
+
+// Every input word must start some word of the name, in any order:
+// "basit", "abdul basit" and "basit abdul" all find "Abdul Basit Abdul Samad".
+// A style name ("murattal") still matches on its own.
+pub fn resolve_reciter<'a>(reciters: &'a [Reciter], spec: &str) -> crate::error::Result<&'a Reciter> {
+    let tokens = norm_words(spec);
+    let key = norm_key(spec);
+    let found = match spec.parse::<u32>() {
+        Ok(n) => reciters.iter().find(|r| r.id == n),
+        Err(_) if tokens.is_empty() => None,
+        Err(_) => reciters.iter().find(|r| {
+            let words = norm_words(&r.reciter_name);
+            let s = r.style.as_deref().map(norm_key).unwrap_or_default();
+            tokens.iter().all(|t| words.iter().any(|w| w.starts_with(t.as_str())))
+                || (!s.is_empty() && s == key)
+        }),
+    };
+    found.ok_or_else(|| HifzrError::ReciterNotFound(spec.to_string()))
+}
+
+// A user's own short name from config.toml's [reciter_aliases] ("m" = 7).
+// Numbers are always ids, so an alias named "7" never hides reciter 7.
+pub fn reciter_alias(aliases: &HashMap<String, u32>, spec: &str) -> Option<u32> {
+    let key = norm_key(spec);
+    if key.is_empty() || spec.trim().parse::<u32>().is_ok() { return None; }
+    aliases.iter().find(|(a, _)| norm_key(a) == key).map(|(_, &id)| id)
+}
+
+// resolve_reciter, tolerating typos ("sudays", "alafasy"): a clear best
+// Jaro-Winkler match is taken, otherwise the closest names come back, best first
+pub fn resolve_reciter_fuzzy<'a>(reciters: &'a [Reciter], spec: &str) -> Result<&'a Reciter, Vec<&'a Reciter>> {
+    if let Ok(r) = resolve_reciter(reciters, spec) { return Ok(r); }
+    let tokens = norm_words(spec);
+    if tokens.is_empty() { return Err(Vec::new()); }
+    let score = |r: &Reciter| {
+        let words = norm_words(&r.reciter_name);
+        // "alafasy" should meet "al" + "afasy"
+        let mut cands = words.clone();
+        cands.extend(words.windows(2).map(|w| format!("{}{}", w[0], w[1])));
+        tokens.iter()
+            .map(|t| cands.iter().map(|w| strsim::jaro_winkler(t, w)).fold(0.0, f64::max))
+            .sum::<f64>() / tokens.len() as f64
+    };
+    let mut ranked: Vec<(f64, &Reciter)> = reciters.iter().map(|r| (score(r), r)).filter(|(s, _)| *s >= 0.7).collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.id.cmp(&b.1.id)));
+    if let Some(&(best, r)) = ranked.first() {
+        // several styles of one reciter share a name; only a different name competes
+        let runner_up = ranked.iter().find(|(_, o)| o.reciter_name != r.reciter_name).map_or(0.0, |(s, _)| *s);
+        if best >= 0.9 && best - runner_up >= 0.05 { return Ok(r); }
+    }
+    Err(ranked.into_iter().take(3).map(|(_, r)| r).collect())
+}
+
+// `--reciter random`: any reciter, or any with a matching style, optionally seeded
+pub fn pick_random_reciter<'a>(reciters: &'a [Reciter], style: Option<&str>, seed: Option<u64>) -> Option<&'a Reciter> {
+    use rand::{SeedableRng, seq::IndexedRandom};
+    let want = style.map(norm_key);
+    let pool: Vec<&Reciter> = reciters.iter()
+        .filter(|r| match &want {
+            Some(w) => r.style.as_deref().map(norm_key).as_ref() == Some(w),
+            None => true,
+        })
+        .collect();
+    match seed {
+        Some(s) => pool.choose(&mut rand::rngs::StdRng::seed_from_u64(s)).copied(),
+        None => pool.choose(&mut rand::rng()).copied(),
+    }
+}
+
+pub const CHAPTER_COUNT: u32 = 114;
+
+// "105-114" → (105, 114), inclusive and within the mushaf
+pub fn parse_chapter_range(s: &str) -> Result<(u32, u32), String> {
+    let (a, b) = s.split_once('-').ok_or_else(|| format!("expected FIRST-LAST, got {s:?}"))?;
+    let a: u32 = a.trim().parse().map_err(|_| format!("bad chapter number {a:?}"))?;
+    let b: u32 = b.trim().parse().map_err(|_| format!("bad chapter number {b:?}"))?;
+    if a == 0 || b > CHAPTER_COUNT {
+        return Err(format!("chapters run from 1 to {CHAPTER_COUNT}, got {s:?}"));
+    }
+    if a > b { return Err(format!("range is reversed: {s:?}")); }
+    Ok((a, b))
 }
 
 // build canonical folder slug for a surah from SERVER data
 pub fn chapter_slug(c: &Chapter) -> String {
     // include the number to avoid ambiguous duplicates between translations
     format!("{}-{:03}", slugify(&c.name_simple), c.id)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reciter(id: u32, name: &str, style: Option<&str>) -> Reciter {
+        Reciter { id, reciter_name: name.into(), style: style.map(Into::into) }
+    }
+
+    fn reciters() -> Vec<Reciter> {
+        vec![
+            reciter(2, "AbdulBaset AbdulSamad", Some("Murattal")),
+            reciter(3, "Abdur-Rahman as-Sudais", None),
+            reciter(4, "Abu Bakr al-Shatri", None),
+            reciter(6, "Mahmoud Khalil Al-Husary", None),
+            reciter(7, "Mishari Rashid al-`Afasy", None),
+            reciter(10, "Sa`ud ash-Shuraym", None),
+        ]
+    }
+
+    fn id_of(spec: &str) -> Option<u32> {
+        resolve_reciter(&reciters(), spec).ok().map(|r| r.id)
+    }
+
+    #[test]
+    fn reciters_by_id_and_multi_word_names() {
+        assert_eq!(id_of("6"), Some(6));
+        assert_eq!(id_of("Mahmoud Khalil Al-Husary"), Some(6));
+        // any order, punctuation and case ignored
+        assert_eq!(id_of("husary mahmoud"), Some(6));
+        assert_eq!(id_of("abdur rahman sudais"), Some(3));
+        assert_eq!(id_of("saud shuraym"), Some(10));
+        // every word has to match
+        assert_eq!(id_of("mahmoud sudais"), None);
+        assert_eq!(id_of("99"), None);
+        assert_eq!(id_of(""), None);
+    }
+
+    #[test]
+    fn reciter_words_match_by_prefix() {
+        assert_eq!(id_of("shat"), Some(4));
+        assert_eq!(id_of("mish afa"), Some(7));
+        assert_eq!(id_of("abu bak"), Some(4));
+        // a prefix of a word, not a piece from its middle
+        assert_eq!(id_of("udais"), None);
+        // a style alone picks a reciter with it
+        assert_eq!(id_of("murattal"), Some(2));
+    }
+
+    #[test]
+    fn aliases_apply_to_names_only() {
+        let aliases = HashMap::from([("m".to_string(), 7), ("6".to_string(), 3), ("Su-Dais".to_string(), 10)]);
+        assert_eq!(reciter_alias(&aliases, "m"), Some(7));
+        assert_eq!(reciter_alias(&aliases, "M"), Some(7));
+        // matched like names: case and punctuation don't count
+        assert_eq!(reciter_alias(&aliases, "sudais"), Some(10));
+        // a number is always an id
+        assert_eq!(reciter_alias(&aliases, "6"), None);
+        assert_eq!(reciter_alias(&aliases, "mishari"), None);
+        assert_eq!(reciter_alias(&aliases, ""), None);
+    }
+
+    fn chapter(id: u32, simple: &str, complex: &str, meaning: &str, arabic: &str) -> Chapter {
+        Chapter {
+            id, name_simple: simple.into(), name_complex: complex.into(), verses_count: None,
+            translated_name: TranslatedName { name: meaning.into() }, name_arabic: arabic.into(),
+            revelation_place: None,
+        }
+    }
+
+    fn chapters() -> Vec<Chapter> {
+        vec![
+            chapter(1, "Al-Fatihah", "Al-Fātiĥah", "The Opener", "الفاتحة"),
+            chapter(2, "Al-Baqarah", "Al-Baqarah", "The Cow", "البقرة"),
+            chapter(36, "Ya-Sin", "Yā-Sīn", "Ya Sin", "يس"),
+            chapter(112, "Al-Ikhlas", "Al-'Ikhlāş", "The Sincerity", "الإخلاص"),
+        ]
+    }
+
+    fn chapter_of(spec: &str) -> Option<u32> {
+        resolve_chapter(&chapters(), spec).ok().map(|c| c.id)
+    }
+
+    #[test]
+    fn chapters_by_number_and_name() {
+        assert_eq!(chapter_of("36"), Some(36));
+        assert_eq!(chapter_of("al-baqarah"), Some(2));
+        assert_eq!(chapter_of("ALBAQARAH"), Some(2));
+        assert_eq!(chapter_of("Yā-Sīn"), Some(36));
+        assert_eq!(chapter_of("115"), None);
+    }
+
+    #[test]
+    fn chapters_by_meaning() {
+        assert_eq!(chapter_of("the cow"), Some(2));
+        assert_eq!(chapter_of("The Sincerity"), Some(112));
+        assert_eq!(chapter_of("opener"), None, "meanings match whole, not in part");
+    }
+
+    #[test]
+    fn chapters_by_arabic_name() {
+        assert_eq!(chapter_of("البقرة"), Some(2));
+        assert_eq!(chapter_of(" يس "), Some(36));
+        assert_eq!(chapter_of("بقرة"), None);
+    }
+
+    // the shape of /chapters?language=en, trimmed to two entries
+    const CHAPTERS_JSON: &str = r#"{"chapters": [
+        {"id": 2, "revelation_place": "madinah", "revelation_order": 87, "bismillah_pre": true,
+         "name_simple": "Al-Baqarah", "name_complex": "Al-Baqarah", "name_arabic": "البقرة",
+         "verses_count": 286, "pages": [2, 49],
+         "translated_name": {"language_name": "english", "name": "The Cow"}},
+        {"id": 112, "name_simple": "Al-Ikhlas"}
+    ]}"#;
+
+    #[test]
+    fn chapter_json_carries_the_ayah_count() {
+        #[derive(Deserialize)] struct R { chapters: Vec<Chapter> }
+        let list = serde_json::from_str::<R>(CHAPTERS_JSON).unwrap().chapters;
+        let baqarah = resolve_chapter(&list, "2").unwrap();
+        assert_eq!(baqarah.verses_count, Some(286));
+        assert_eq!(baqarah.translated_name.name, "The Cow");
+        assert_eq!(baqarah.revelation_place.as_deref(), Some("madinah"));
+        // fields the API leaves out stay unknown (or empty for names)
+        let ikhlas = resolve_chapter(&list, "112").unwrap();
+        assert_eq!(ikhlas.verses_count, None);
+        assert_eq!(ikhlas.revelation_place, None);
+        assert!(ikhlas.name_arabic.is_empty());
+    }
+}
@@ -1,16 +1,16 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use reqwest::Client;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // ✨ colors
 use owo_colors::OwoColorize;
 
-mod models;
-mod api;
-mod download;
-mod hifz;
-mod lookup;
+use hifzr::{api, download, event, ffmpeg, hifz, library, lookup, probe};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use hifzr::error::HifzrError;
+
+mod config;
 
 #[derive(Parser)]
 #[command(
@@ -24,51 +24,512 @@ mod lookup;
 struct Cli {
     #[command(subcommand)]
     cmd: Cmd,
+
+    /// Machine-readable output for commands that report data
+    #[arg(long, global = true, default_value_t=false)] json: bool,
+
+    /// Abort the whole run (exit 75) if the API keeps rate-limiting us
+    #[arg(long, global = true, default_value_t=false)] stop_on_429: bool,
+
+    /// Give up downloading after this many seconds in total (exit 124)
+    #[arg(long, global = true)] deadline: Option<u64>,
+
+    /// Abort any single ayah download larger than this (e.g. 50m, 1g)
+    #[arg(long, global = true, default_value="50m", value_parser = download::parse_size)] max_file_size: u64,
+
+    /// Cap the total download speed in KB/s (1 KB = 1024 bytes); 0 means no limit
+    #[arg(long, global = true, value_name = "KB/s", default_value_t=0)] max_rate: u64,
+
+    /// Let 429s steer how many ayahs download at once, from --jobs up to at most N
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=32))] max_jobs: Option<u32>,
+
+    /// With --max-jobs: never fewer than N at once
+    #[arg(long, global = true, value_name = "N", default_value_t=1, value_parser = clap::value_parser!(u32).range(1..=32))] min_jobs: u32,
+
+    /// Proxy for every request (http://, https:// or socks5://); else $HTTPS_PROXY/$HTTP_PROXY
+    #[arg(long, global = true, value_name = "URL")] proxy: Option<String>,
+
+    /// Seconds a request may go without any data before it fails (and is retried)
+    #[arg(long, global = true, default_value_t=30, value_parser = clap::value_parser!(u64).range(1..))] timeout: u64,
+
+    /// Keep interrupted downloads as .part files and resume them next run (HTTP Range)
+    #[arg(long, global = true, default_value_t=false)] resume_partial: bool,
+
+    /// Refetch the chapter/reciter lists instead of using the week-old cache
+    #[arg(long, global = true, default_value_t=false)] refresh: bool,
+
+    /// How ayah files are named: {ayah:03} (001.mp3), {surah}_{ayah:03}, {verse_key} (2_255.mp3)
+    #[arg(long, global = true, default_value = "{ayah:03}")] name_template: hifz::NameTemplate,
+
+    /// ffmpeg binary to use [default: $HIFZR_FFMPEG, else ffmpeg on PATH]
+    #[arg(long, global = true, value_name = "PATH")] ffmpeg: Option<String>,
+
+    /// Leave Arabic surah names out of the output (for terminals that garble RTL text)
+    #[arg(long, global = true, default_value_t=false)] no_arabic: bool,
+
+    /// Retries after a rate limit (429) or a dropped connection before giving up
+    #[arg(long, global = true, default_value_t=5)] max_retries: u32,
+
+    /// First wait between retries in milliseconds; doubles each retry (a Retry-After header wins)
+    #[arg(long, global = true, default_value_t=500)] retry_backoff_ms: u64,
+
+    /// Debugging: fetch at most N pages (50 verses each) of verse metadata
+    #[arg(long, global = true, hide = true)] max_pages: Option<u32>,
 }
 
 #[derive(Subcommand)]
 enum Cmd {
     /// Download ayahs for a given chapter/reciter into a neat folder
     Download {
+        /// Name or id, or "random" to pick one
         #[arg(long)] reciter: String,
-        #[arg(long)] chapter: String,
+        /// Name or id; "2:255" or "2:255-257" picks just those ayahs; "all" or "juz:30" for many surahs
+        #[arg(long, required_unless_present_any = ["collection", "chapter_range", "reference"])] chapter: Option<String>,
+        /// Ayah reference like "2:255" or "2:255-257" (same as putting it in --chapter)
+        #[arg(long = "ref", conflicts_with_all = ["chapter", "chapter_range", "collection"])] reference: Option<String>,
+        /// Several surahs in a row by number, e.g. "105-114"
+        #[arg(long, conflicts_with_all = ["chapter", "collection"], value_parser = lookup::parse_chapter_range)]
+        chapter_range: Option<(u32, u32)>,
+        /// Non-surah verse listing instead: API path (e.g. "verses/by_juz/30") or full URL
+        #[arg(long, conflicts_with = "chapter")] collection: Option<String>,
+        /// Folder name for --collection (defaults to a slug of it)
+        #[arg(long, requires = "collection")] name: Option<String>,
         #[arg(long, default_value="~/Quran_hifz")] out: String,
         #[arg(long, default_value_t=false)] force: bool,
+        /// Refresh <out>/library.json afterwards
+        #[arg(long, default_value_t=false)] index: bool,
+        /// Write <surah>/info.txt (name, ayah count, revelation place); `--write-info false` to skip
+        #[arg(long, default_value_t=true, action = clap::ArgAction::Set)] write_info: bool,
+        /// Report overlapping/out-of-order segment timings and ones past the file's end
+        #[arg(long, default_value_t=false)] verify_segments: bool,
+        /// Skip ayahs that have no word segment timings for this reciter
+        #[arg(long, default_value_t=false)] require_segments: bool,
+        /// Ayahs to download at once
+        #[arg(long, default_value_t=4, value_parser = clap::value_parser!(u32).range(1..=32))] jobs: u32,
+        /// Don't stop at a failed ayah; list failures in <folder>/retry.json for `hifzr retry`
+        #[arg(long, default_value_t=false)] keep_going: bool,
+        /// Desktop notification when the download finishes
+        #[arg(long, default_value_t=false)] notify: bool,
+        /// With several surahs, fetch up to N verse listings at once ahead of the audio
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=16))] prefetch: Option<u32>,
+        /// Only report how many ayahs (and roughly how many bytes) would be fetched
+        #[arg(long, default_value_t=false)] count: bool,
+        /// List each target file and its audio URL, marked get or skip; writes nothing
+        #[arg(long, default_value_t=false, conflicts_with = "count")] dry_run: bool,
+        /// Print the estimated total size of the ayahs still to fetch before starting
+        #[arg(long, default_value_t=false)] estimate: bool,
+        /// Also show a byte progress bar for each file being downloaded
+        #[arg(long, default_value_t=false)] file_progress: bool,
+        /// Also cut each ayah into per-word clips under words/ (needs ffmpeg)
+        #[arg(long, default_value_t=false)] words: bool,
+        /// Save each ayah's tafsir as NNN.tafsir.txt (ids from `hifzr ls tafsirs`)
+        #[arg(long, value_name = "ID")] tafsir: Option<u32>,
+        /// Save each ayah's translation as NNN.translation.txt (ids from `hifzr ls translations`)
+        #[arg(long, value_name = "ID")] translation: Option<u32>,
+        /// Also transcode each ayah to low-bitrate Opus under DIR (same layout as --out)
+        #[arg(long, value_name = "DIR")] phone_copy: Option<String>,
+        /// Transcode each ayah to this format next to the mp3 (needs ffmpeg)
+        #[arg(long, value_enum)] convert: Option<ConvertArg>,
+        /// With --convert, delete each mp3 once its converted copy is written
+        #[arg(long, default_value_t=false, requires = "convert")] delete_source: bool,
+        #[command(flatten)] loudness: Loudness,
+        #[command(flatten)] window: AyahWindow,
+
+        #[command(flatten)] pick: RandomPick,
+    },
+    /// Download again exactly what a --keep-going run listed in retry.json
+    Retry {
+        file: PathBuf,
+    },
+    /// Cut the ayahs in a folder into per-word clips from their segments.json (offline)
+    Words {
+        /// A surah/reciter folder from `download`
+        dir: PathBuf,
+        /// Cut again even where a clip exists
+        #[arg(long, default_value_t=false)] force: bool,
+    },
+    /// Check a surah folder for missing, empty or changed ayahs
+    Verify {
+        #[arg(long)] chapter: String,
+        #[arg(long)] reciter: String,
+        #[arg(long, default_value="~/Quran_hifz")] out: String,
+        /// Re-download only the ayahs that failed the check
+        #[arg(long, default_value_t=false)] repair: bool,
+        /// Ayahs to download at once with --repair
+        #[arg(long, default_value_t=4, value_parser = clap::value_parser!(u32).range(1..=32))] jobs: u32,
+    },
+    /// Delete a surah's downloaded audio (one reciter or all), or just its playlists
+    Clean {
+        #[arg(long)] chapter: String,
+        #[arg(long, required_unless_present = "all")] reciter: Option<String>,
+        /// The whole surah folder, every reciter
+        #[arg(long, conflicts_with = "reciter", default_value_t=false)] all: bool,
+        #[arg(long, default_value="~/Quran_hifz")] out: String,
+        /// Keep the audio; remove only playlists, latest_playlist.txt and gap files
+        #[arg(long, default_value_t=false)] playlists_only: bool,
+        /// Don't ask before deleting
+        #[arg(long, short = 'y', default_value_t=false)] yes: bool,
     },
     /// Build an ayah-only playlist (optionally auto-download first)
     Hifz {
-        #[arg(long)] chapter: String,
+        /// Name or id; several ("93,94,95" or "78-114") make one combined playlist,
+        /// "2:255" or "2:255-257" is one surah with those --verses
+        #[arg(long, required_unless_present_any = ["juz", "reference"])] chapter: Option<String>,
+        /// Ayah reference like "2:255" or "2:255-257" (same as putting it in --chapter)
+        #[arg(long = "ref", conflicts_with_all = ["chapter", "verses", "juz"])] reference: Option<String>,
         /// "1-5,7,10-12"; if omitted we scan the folder
         #[arg(long)] verses: Option<String>,
+        /// Whole juz across its surahs into one playlist (needs --reciter)
+        #[arg(long, conflicts_with_all = ["chapter", "verses"], requires = "reciter",
+              value_parser = clap::value_parser!(u32).range(1..=30))]
+        juz: Option<u32>,
 
-        /// Auto-download missing files first (needs --reciter)
-        #[arg(long, default_value_t=false)] auto_download: bool,
+        #[command(flatten)] opts: HifzOpts,
+    },
+    /// Sliding-window playlist: session N covers `window` ayahs from 1+(N-1)·step
+    Window {
+        #[arg(long)] chapter: String,
+        /// Ayahs per session
+        #[arg(long, default_value_t=3)] window: u32,
+        /// How far the window moves each session
+        #[arg(long, default_value_t=1)] step: u32,
+        /// 1-based session number
+        #[arg(long)] session: u32,
+
+        #[command(flatten)] opts: HifzOpts,
+    },
+    /// Inspect a downloaded file (duration, bitrate, format sanity)
+    Probe {
+        /// Path to an audio file; or locate it with --chapter/--reciter/--ayah
+        #[arg(conflicts_with_all = ["chapter", "ayah"], required_unless_present = "chapter")]
+        path: Option<PathBuf>,
+        #[arg(long, requires_all = ["reciter", "ayah"])] chapter: Option<String>,
         #[arg(long)] reciter: Option<String>,
+        #[arg(long)] ayah: Option<u32>,
+        #[arg(long, default_value="~/Quran_hifz")] out: String,
+    },
+    /// Pre-generate (or regenerate) the silence file for a gap length
+    Silence {
+        #[arg(long)] gap_ms: u32,
+        /// Folder to write it into (usually a surah/reciter folder)
+        #[arg(long)] out: String,
+        /// Regenerate even if the file already exists
         #[arg(long, default_value_t=false)] force: bool,
-
-        /// Repeats per ayah
-        #[arg(long, default_value_t=3)] repeat: usize,
-
-        /// Optional silence (ms) between repeats/ayahs (uses a tiny silent file)
-        #[arg(long, default_value_t=0)] gap_ms: u32,
-
+    },
+    /// (Re)write <out>/library.json describing everything downloaded
+    Index {
+        #[arg(long, default_value="~/Quran_hifz")] out: String,
+    },
+    /// Show what's downloaded: surah → reciter → ayah count
+    Tree {
+        #[arg(long, default_value="~/Quran_hifz")] out: String,
+    },
+    /// Open a playlist in mpv/vlc (the folder's latest one by default)
+    Play {
+        /// Playlist file or folder; defaults to the current folder
+        #[arg(conflicts_with = "chapter")] path: Option<PathBuf>,
+        /// Find the surah/reciter folder instead (needs --reciter)
+        #[arg(long, requires = "reciter")] chapter: Option<String>,
+        #[arg(long)] reciter: Option<String>,
         #[arg(long, default_value="~/Quran_hifz")] out: String,
+        /// Player command; otherwise $HIFZR_PLAYER, then mpv, vlc, cvlc
+        #[arg(long)] player: Option<String>,
+        /// Loop the whole playlist
+        #[arg(long = "loop", default_value_t=false)] repeat: bool,
     },
-    /// List chapters or reciters
+    /// Write a printable "[ ] 2:255" checklist of the selected ayahs
+    Checklist {
+        #[arg(long)] chapter: String,
+        /// e.g. "1-20"; defaults to the whole surah
+        #[arg(long)] verses: Option<String>,
+        /// Where to write it
+        #[arg(long, default_value="checklist.txt")] output: String,
+    },
+    /// Time a throwaway download of one surah to check network/CDN speed
+    Benchmark {
+        /// Reciter id or name (or "random")
+        #[arg(long)] reciter: String,
+        /// A short surah keeps it quick
+        #[arg(long, default_value="1")] chapter: String,
+        #[command(flatten)] pick: RandomPick,
+    },
+    /// List chapters, reciters, tafsirs or translations
     Ls {
         #[arg(value_enum)] what: ListWhat,
+        /// Order of `ls chapters`: by number, by name, or shortest first
+        #[arg(long, value_enum, default_value_t=SortBy::Id)] sort: SortBy,
+        /// Only entries whose name contains this (case and diacritics ignored)
+        #[arg(long)] filter: Option<String>,
+    },
+    /// Check the tools hifzr leans on (ffmpeg, ffprobe) and say what's missing
+    Doctor,
+    /// About the config file (defaults for --reciter, --out, --jobs, ...)
+    Config {
+        #[command(subcommand)] what: ConfigCmd,
     },
 }
 
+#[derive(Subcommand)]
+enum ConfigCmd {
+    /// Print where the config file is read from
+    Path,
+    /// Print the config in effect (file values plus built-in defaults) as TOML
+    Dump,
+}
+
+/// Constraints for `--reciter random`
+#[derive(Args, Clone)]
+struct RandomPick {
+    /// Only pick among reciters with this style (e.g. murattal)
+    #[arg(long)] style: Option<String>,
+    /// Seed for a reproducible pick (and `hifz --shuffle` order)
+    #[arg(long)] seed: Option<u64>,
+}
+
+/// Loudness normalization for downloads and `--concat`
+#[derive(Args, Clone)]
+struct Loudness {
+    /// Even out the volume of new downloads (and a --concat file) with ffmpeg's two-pass loudnorm
+    #[arg(long, default_value_t=false)] normalize: bool,
+    /// Target integrated loudness for --normalize, in LUFS
+    #[arg(long, default_value_t=-16.0, allow_negative_numbers = true, requires = "normalize")] lufs: f64,
+}
+
+impl Loudness {
+    // The LUFS target, or None without --normalize
+    fn target(&self) -> Result<Option<f64>> {
+        if !self.normalize { return Ok(None); }
+        // loudnorm's own limits
+        if !(-70.0..=-5.0).contains(&self.lufs) {
+            anyhow::bail!("{} {}", "--lufs must be between -70 and -5, got".red().bold(), self.lufs);
+        }
+        Ok(Some(self.lufs))
+    }
+}
+
+/// A slice of the selected ayahs, for drilling a few at a time
+#[derive(Args, Clone)]
+struct AyahWindow {
+    /// Skip the first N ayahs of the selection (--verses, or the whole surah)
+    #[arg(long, value_name = "N")] offset: Option<usize>,
+    /// Then keep at most M of them
+    #[arg(long, value_name = "M", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    limit: Option<usize>,
+}
+
+impl AyahWindow {
+    fn is_set(&self) -> bool {
+        self.offset.is_some() || self.limit.is_some()
+    }
+
+    // Cut the list down to the window and say how many that left
+    fn apply(&self, ayahs: Vec<u32>, json: bool) -> Result<Vec<u32>> {
+        let total = ayahs.len();
+        let offset = self.offset.unwrap_or(0);
+        let picked: Vec<u32> = ayahs.into_iter().skip(offset).take(self.limit.unwrap_or(usize::MAX)).collect();
+        if picked.is_empty() {
+            anyhow::bail!("{} {}", format!("--offset {} skips past the end of the selection: it has", offset).red().bold(),
+                format!("{} ayahs", total).red().bold());
+        }
+        if !json {
+            let short = self.limit.filter(|&l| picked.len() < l)
+                .map(|l| format!(" (only {} of the {} asked for)", picked.len(), l)).unwrap_or_default();
+            println!("{} {} {} {}{}", "".bright_black(), label("Window:"), hifz::format_verses_ordered(&picked).bold(),
+                format!("{} of {} ayahs", picked.len(), total).dimmed(), short.yellow());
+        }
+        Ok(picked)
+    }
+
+    // The same for a --verses spec, handed on as a spec again
+    fn apply_spec(&self, spec: &str, max: Option<u32>, json: bool) -> Result<String> {
+        let ayahs = self.apply(hifz::parse_verses_spec(spec, max)?, json)?;
+        Ok(hifz::format_verses_ordered(&ayahs))
+    }
+}
+
+/// Playlist knobs shared by `hifz` and its study-mode variants
+#[derive(Args)]
+struct HifzOpts {
+    /// Auto-download missing files first (needs --reciter)
+    #[arg(long, default_value_t=false)] auto_download: bool,
+    /// Name or id, or "random" to pick one
+    #[arg(long)] reciter: Option<String>,
+    /// Second reciter for --interleave (downloaded like --reciter)
+    #[arg(long, requires = "interleave")] reciter_b: Option<String>,
+    /// Play each ayah from --reciter, then from --reciter-b
+    #[arg(long, default_value_t=false, requires = "reciter_b", conflicts_with = "append_new")] interleave: bool,
+    #[arg(long, default_value_t=false)] force: bool,
+    /// Write <surah>/info.txt when auto-downloading; `--write-info false` to skip
+    #[arg(long, default_value_t=true, action = clap::ArgAction::Set)] write_info: bool,
+
+    #[command(flatten)] pick: RandomPick,
+
+    /// Named defaults for repeat/gap; explicit flags still win
+    #[arg(long, value_enum)] preset: Option<Preset>,
+
+    /// Repeats per ayah, or per-ayah counts like "1:10,2:3,*:5" [default: 3]
+    #[arg(long)] repeat: Option<hifz::RepeatSpec>,
+
+    /// Repeats per ayah from --reciter-b [default: --repeat]
+    #[arg(long, requires = "interleave", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    repeat_b: Option<usize>,
+
+    /// Build up: ayah 1, then 1-2, then 1-3, ... (each pass with its repeats)
+    #[arg(long, default_value_t=false, conflicts_with = "append_new")] cumulative: bool,
+
+    /// Silence (ms) between repeats of an ayah (uses a tiny silent file) [default: 0]
+    #[arg(long)] gap_ms: Option<u32>,
+
+    /// Silence (ms) between different ayahs [default: --gap-ms]
+    #[arg(long)] between_ayah_gap: Option<u32>,
+
+    /// What plays in the gaps
+    #[arg(long, value_enum, default_value_t=GapKind::Silence)] gap_sound: GapKind,
+
+    /// Pitch of --gap-sound tone
+    #[arg(long, default_value_t=440)] tone_hz: u32,
+
+    /// Don't put gap files in the playlist (let the player pause); gaps only lengthen #EXTINF
+    #[arg(long, default_value_t=false)] no_silence_file_in_playlist: bool,
+
+    /// Silence after each ayah as a fraction of its length (e.g. 0.3); needs ffprobe, falls back to --gap-ms
+    #[arg(long)] gap_ratio: Option<f32>,
+
+    #[arg(long, default_value="~/Quran_hifz")] out: String,
+
+    /// Print the entries the playlist would get; writes and downloads nothing
+    #[arg(long, default_value_t=false)] dry_run: bool,
+
+    /// Print the resolved selection as a --verses spec (e.g. "1-148") and stop
+    #[arg(long, default_value_t=false)] print_spec: bool,
+
+    /// Just print how many ayahs/plays the selection comes to (and its length); no downloads
+    #[arg(long, default_value_t=false)] count: bool,
+
+    #[command(flatten)] window: AyahWindow,
+
+    /// Only the N longest/shortest ayahs, e.g. "longest:5" (needs ffprobe)
+    #[arg(long)] select: Option<hifz::Select>,
+
+    /// Write entries as PREFIX/<path under --out>, e.g. /storage/emulated/0/Music/Quran_hifz
+    #[arg(long)] path_prefix: Option<String>,
+
+    /// Play order; duration-asc ramps from the shortest ayah up (needs ffprobe)
+    #[arg(long, value_enum, default_value_t=OrderArg::Ayah)] order: OrderArg,
+
+    /// Play the ayahs in random order to test recall; --seed replays an order
+    #[arg(long, default_value_t=false, conflicts_with = "order")] shuffle: bool,
+
+    /// With --shuffle, scatter each ayah's repeats too instead of keeping them together
+    #[arg(long, default_value_t=false, requires = "shuffle", conflicts_with = "cumulative")] shuffle_repeats: bool,
+
+    /// Playlist file type: m3u, pls (older players) or m3u8 (titles and lengths)
+    #[arg(long, value_enum, default_value_t=FormatArg::M3u)] format: FormatArg,
+
+    /// Write playlists into DIR (with absolute paths) instead of the audio folder
+    #[arg(long, value_name = "DIR")] playlist_dir: Option<String>,
+
+    /// Number of the file holding ayah 1, for folders made by other tools (e.g. 0 for 000.mp3)
+    #[arg(long, default_value_t=1)] ayah_numbers_from: u32,
+
+    /// Ayahs to download at once with --auto-download
+    #[arg(long, default_value_t=4, value_parser = clap::value_parser!(u32).range(1..=32))] jobs: u32,
+
+    /// Only ayahs with word segment timings (for word-by-word highlighting)
+    #[arg(long, default_value_t=false)] require_segments: bool,
+
+    /// Also join the playlist (repeats and gaps baked in) into one surah_hifz.mp3 (needs ffmpeg)
+    #[arg(long, default_value_t=false)] concat: bool,
+
+    /// With --concat: always re-encode, trimming each piece's encoder padding so the joins don't click
+    #[arg(long, default_value_t=false, requires = "concat")] gapless: bool,
+
+    #[command(flatten)] loudness: Loudness,
+
+    /// Keep the existing playlist and append only ayahs it doesn't play yet
+    #[arg(long, default_value_t=false)] append_new: bool,
+
+    /// Build from the --verses that are on disk only; list the rest in missing.txt
+    #[arg(long, default_value_t=false)] playlist_only_existing: bool,
+}
+
+/// Built-in starting points for repeat/gap
+#[derive(Copy, Clone, Eq, PartialEq, ValueEnum)]
+enum Preset {
+    /// repeat 5, gap 1500 ms
+    Beginner,
+    /// repeat 1, gap 500 ms
+    Review,
+}
+
+impl Preset {
+    fn repeat(self) -> usize {
+        match self { Preset::Beginner => 5, Preset::Review => 1 }
+    }
+    fn gap_ms(self) -> u32 {
+        match self { Preset::Beginner => 1500, Preset::Review => 500 }
+    }
+}
+
+impl HifzOpts {
+    // flag > preset > config file > built-in default
+    fn repeat(&self) -> usize {
+        self.repeat.as_ref().and_then(|r| r.default).or(self.preset.map(Preset::repeat)).or(config::get().repeat).unwrap_or(config::REPEAT)
+    }
+    fn gap_ms(&self) -> u32 {
+        self.gap_ms.or(self.preset.map(Preset::gap_ms)).or(config::get().gap_ms).unwrap_or(config::GAP_MS)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, ValueEnum)]
+enum GapKind { Silence, Tone }
+
+#[derive(Copy, Clone, Eq, PartialEq, ValueEnum)]
+enum OrderArg { Ayah, DurationAsc, DurationDesc }
+
+#[derive(Copy, Clone, Eq, PartialEq, ValueEnum)]
+enum FormatArg {
+    M3u,
+    Pls,
+    #[value(name = "m3u8", alias = "ext-m3u")]
+    ExtM3u,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, ValueEnum)]
+enum ConvertArg { Opus, Ogg, M4a }
+
+impl ConvertArg {
+    fn codec(self) -> download::Convert {
+        match self {
+            ConvertArg::Opus => download::Convert::Opus,
+            ConvertArg::Ogg => download::Convert::Ogg,
+            ConvertArg::M4a => download::Convert::M4a,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, ValueEnum)]
+enum ListWhat { Chapters, Reciters, Tafsirs, Translations }
+
 #[derive(Copy, Clone, Eq, PartialEq, ValueEnum)]
-enum ListWhat { Chapters, Reciters }
+enum SortBy { Id, Name, Length }
 
 // ---------- small UI helpers ----------
+// dirs knows each platform's home; USERPROFILE covers a Windows setup where
+// the known-folder lookup fails
+fn home_dir() -> Option<PathBuf> {
+    dirs::home_dir().or_else(|| std::env::var_os("USERPROFILE").filter(|v| !v.is_empty()).map(PathBuf::from))
+}
+
 fn expand_tilde(p: &str) -> String {
-    if p.starts_with("~/") {
-        if let Some(home) = std::env::var_os("HOME") {
-            return PathBuf::from(home).join(&p[2..]).to_string_lossy().to_string();
-        }
+    // "~\Music" is how the same path gets typed on Windows
+    let rest = p.strip_prefix("~/").or_else(|| p.strip_prefix("~\\").filter(|_| cfg!(windows)));
+    if let Some(rest) = rest
+        && let Some(home) = home_dir() {
+        return home.join(rest).to_string_lossy().to_string();
+    }
+    if p == "~" && let Some(home) = home_dir() {
+        return home.to_string_lossy().to_string();
     }
     p.to_string()
 }
@@ -82,6 +543,262 @@ fn per_surah_base(out: &str, surah_slug: &str, reciter_slug: &str) -> String {
 
 fn label(s: &str) -> String { s.dimmed().to_string() }
 
+static NO_ARABIC: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// " الفاتحة" to follow a Latin name, or nothing with --no-arabic. The bidi
+// isolate (U+2068…U+2069) keeps the RTL run from reordering the text around it.
+fn arabic_name(c: &lookup::Chapter) -> String {
+    if NO_ARABIC.load(std::sync::atomic::Ordering::Relaxed) || c.name_arabic.is_empty() {
+        return String::new();
+    }
+    format!(" \u{2068}{}\u{2069}", c.name_arabic)
+}
+
+// What the library reports goes here. On a terminal a download pass draws
+// indicatif bars; piped, or with NO_COLOR, the plain "prepping" counter.
+fn progress() -> &'static event::Sink {
+    static SINK: std::sync::OnceLock<event::Sink> = std::sync::OnceLock::new();
+    SINK.get_or_init(|| {
+        use std::io::IsTerminal;
+        let fancy = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        let bars = std::sync::Mutex::new(None);
+        event::Sink::new(move |e| show_event(e, fancy, &mut bars.lock().unwrap()))
+    })
+}
+
+struct Bars {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    /// --file-progress: a byte bar per file in flight, above the overall one
+    files: std::collections::HashMap<String, ProgressBar>,
+}
+
+fn show_event(e: event::Event, fancy: bool, bars: &mut Option<Bars>) {
+    use event::Event;
+    // a line that doesn't tear through the bars
+    let line = |bars: &Option<Bars>, msg: String| match bars {
+        Some(b) => { let _ = b.multi.println(msg); }
+        None => eprintln!("\r{}", msg),
+    };
+    match e {
+        Event::Note(msg) => line(bars, msg),
+        Event::Warning(msg) => line(bars, format!("{} {}", "warning:".yellow(), msg)),
+        Event::Started { total } if fancy => {
+            let multi = MultiProgress::new();
+            let overall = multi.add(ProgressBar::new(total as u64));
+            overall.set_style(ProgressStyle::with_template("{bar:30} {pos}/{len} ayahs {msg} (eta {eta})")
+                .expect("valid template"));
+            *bars = Some(Bars { multi, overall, files: Default::default() });
+        }
+        Event::Started { .. } => {}
+        Event::FileBytes { file, done, total } => {
+            let Some(b) = bars else { return };
+            let bar = b.files.entry(file).or_insert_with_key(|name| {
+                let bar = b.multi.insert_before(&b.overall, ProgressBar::new(0));
+                bar.set_style(ProgressStyle::with_template("  {msg} {bar:20} {bytes}/{total_bytes} {bytes_per_sec}")
+                    .expect("valid template"));
+                bar.set_message(name.clone());
+                bar
+            });
+            if let Some(t) = total { bar.set_length(t); }
+            bar.set_position(done);
+        }
+        Event::FileDone { file } => {
+            if let Some(bar) = bars.as_mut().and_then(|b| b.files.remove(&file)) { bar.finish_and_clear(); }
+        }
+        Event::AyahDone { ayah, stem, done, total } => match bars {
+            Some(b) => {
+                b.overall.set_message(format!("(last {stem})"));
+                b.overall.inc(1);
+            }
+            None => eprint!("\rprepping {:03}: {}/{}", ayah, done, total),
+        },
+        Event::Sizing { source, ayahs } => eprint!("\rsizing {}: {} ayahs", source, ayahs),
+        Event::Finished => match bars.take() {
+            Some(b) => b.overall.finish_and_clear(),
+            None => eprintln!(),
+        },
+        Event::Planned { path, url, exists } => {
+            println!("{:<4} {}  {}", if exists { "skip" } else { "get" }, path.display(), url);
+        }
+        Event::PlanTotal { get, skip } => println!("dry run: {} to download, {} already there", get, skip),
+    }
+}
+
+// `download --count`: ayahs plus the HEAD-reported size, summed over surahs
+fn print_download_count(counts: &[download::Count]) {
+    let ayahs: usize = counts.iter().map(|c| c.ayahs).sum();
+    let bytes: u64 = counts.iter().map(|c| c.bytes).sum();
+    let unknown: usize = counts.iter().map(|c| c.unknown_size).sum();
+    println!("{} {} {}", "#".cyan(), label("Count:"), format!("{} ayahs", ayahs).bold());
+    let size = format!("≈ {:.1} MiB", bytes as f64 / (1 << 20) as f64);
+    println!(
+        "   {} {}{}",
+        label("Size:"),
+        size.bold(),
+        if unknown > 0 { format!(" (+{} without a size)", unknown).dimmed().to_string() } else { String::new() }
+    );
+}
+
+// best effort: no notification daemon (headless, CI, ssh) is not an error
+fn desktop_notify(body: &str) {
+    let _ = notify_rust::Notification::new()
+        .appname("hifzr")
+        .summary("hifzr")
+        .body(body)
+        .show();
+}
+
+// info.txt is a courtesy; a failure to write it shouldn't fail the download
+fn write_info_file(out: &str, c: &lookup::Chapter, reciter: &str, force: bool) {
+    let dir = PathBuf::from(expand_tilde(out)).join(lookup::chapter_slug(c));
+    match library::write_surah_info(&dir, c, reciter, force) {
+        Ok(Some(path)) => println!("   {} {}", label("Info:"), path.display().bold().blue()),
+        Ok(None) => {}
+        Err(e) => eprintln!("{} {}", "⚠ could not write info.txt:".yellow(), e),
+    }
+}
+
+// Resolve --chapter, suggesting near misses when nothing matches
+fn find_chapter<'a>(chapters: &'a [lookup::Chapter], spec: &str) -> Result<&'a lookup::Chapter> {
+    if let Ok(c) = lookup::resolve_chapter(chapters, spec) {
+        return Ok(c);
+    }
+    let near: Vec<String> = lookup::suggest_chapters(chapters, spec, 3).iter()
+        .map(|c| format!("{} ({})", c.name_simple, c.id))
+        .collect();
+    if near.is_empty() {
+        anyhow::bail!("{} {}", "Unknown chapter:".red().bold(), spec.bold());
+    }
+    anyhow::bail!("{} {} — did you mean: {}?", "Unknown chapter:".red().bold(), spec.bold(), near.join(", ").bold());
+}
+
+// --chapter for hifz: one surah, or several as "93,94,95" / "78-114"
+fn find_chapters<'a>(chapters: &'a [lookup::Chapter], spec: &str) -> Result<Vec<&'a lookup::Chapter>> {
+    let mut out = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        // only digits on both sides make a range; names like "al-fil" have dashes too
+        let numeric = part.split_once('-')
+            .is_some_and(|(a, b)| [a, b].iter().all(|x| !x.trim().is_empty() && x.trim().bytes().all(|c| c.is_ascii_digit())));
+        if numeric {
+            let (a, b) = lookup::parse_chapter_range(part).map_err(|e| anyhow::anyhow!("{} {}", "Bad --chapter:".red().bold(), e))?;
+            for id in a..=b {
+                out.push(find_chapter(chapters, &id.to_string())?);
+            }
+        } else {
+            out.push(find_chapter(chapters, part)?);
+        }
+    }
+    if out.is_empty() {
+        anyhow::bail!("{}", "--chapter is empty".red().bold());
+    }
+    Ok(out)
+}
+
+// --ref, or a --chapter written as "2:255" / "2:255-257" → (chapter, verses)
+fn ayah_ref<'a>(reference: Option<&'a str>, chapter: Option<&'a str>) -> Result<Option<(&'a str, &'a str)>> {
+    let Some(r) = reference.or(chapter.filter(|c| c.contains(':'))) else { return Ok(None) };
+    match r.split_once(':').map(|(c, v)| (c.trim(), v.trim())) {
+        Some((c, v)) if !c.is_empty() && !v.is_empty() => Ok(Some((c, v))),
+        _ => anyhow::bail!("{} {} (expected e.g. 2:255 or 2:255-257)", "Bad ayah reference:".red().bold(), r.bold()),
+    }
+}
+
+// Every wanted ayah (all of them when `only` is None) already has its mp3
+fn surah_complete(out_root: &str, c: &lookup::Chapter, only: Option<&[u32]>) -> bool {
+    let Ok(have) = hifz::detect_available_ayahs(Path::new(out_root)) else { return false };
+    match only {
+        Some(want) => want.iter().all(|a| have.contains(a)),
+        None => c.verses_count.is_some_and(|n| have.len() >= n as usize),
+    }
+}
+
+fn check_in_surah(c: &lookup::Chapter, ayahs: &[u32]) -> Result<()> {
+    if let Some(&last) = ayahs.iter().max()
+        && let Some(n) = c.verses_count
+        && last > n {
+        anyhow::bail!("{} {} has {} ayahs, but {} was asked for",
+            "Out of range:".red().bold(), c.name_simple, n, last);
+    }
+    Ok(())
+}
+
+// The playlist `hifz` last wrote in `dir`: latest_playlist.txt, else any hifz_ayah.*
+fn find_playlist(dir: &Path) -> Option<PathBuf> {
+    if let Ok(p) = std::fs::read_to_string(dir.join("latest_playlist.txt")) {
+        let p = PathBuf::from(p.trim());
+        if p.is_file() { return Some(p); }
+    }
+    ["m3u", "m3u8", "pls"].iter()
+        .map(|ext| dir.join(format!("hifz_ayah.{ext}")))
+        .find(|p| p.is_file())
+}
+
+// A command name (looked up in $PATH) or a path to an executable
+fn on_path(cmd: &str) -> bool {
+    if cmd.contains(std::path::MAIN_SEPARATOR) {
+        return Path::new(cmd).is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|d| d.join(cmd).is_file()))
+}
+
+fn tag_info(c: &lookup::Chapter, r: &lookup::Reciter) -> download::TagInfo {
+    download::TagInfo { surah: c.name_simple.clone(), reciter: r.reciter_name.clone() }
+}
+
+// `verify` findings; `last` adds the all-clear line
+fn print_verify(report: &download::VerifyReport, last: bool) {
+    println!("   {} {}/{}", label("Present:"), report.present.to_string().bold(), report.expected);
+    for (name, list) in [("Missing:", &report.missing), ("Empty:", &report.empty), ("Changed:", &report.changed)] {
+        if !list.is_empty() {
+            println!("   {} {}", label(name), hifz::format_verses_spec(list).red().bold());
+        }
+    }
+    if last && report.gaps().is_empty() {
+        println!("{} {}", "✔".green().bold(), "All ayahs present and intact".bold());
+    }
+}
+
+// Resolve --reciter, treating "random" as a sentinel rather than a name
+fn pick_reciter<'a>(reciters: &'a [lookup::Reciter], spec: &str, pick: &RandomPick) -> Result<&'a lookup::Reciter> {
+    if spec.eq_ignore_ascii_case("random") {
+        let r = lookup::pick_random_reciter(reciters, pick.style.as_deref(), pick.seed)
+            .with_context(|| match &pick.style {
+                Some(st) => format!("{} {}", "No reciter with style".red().bold(), st.bold()),
+                None => "No reciters to pick from".red().bold().to_string(),
+            })?;
+        println!(
+            "{} {} {}",
+            "🎲".yellow(),
+            label("Random reciter:"),
+            format!("{} ({})", r.reciter_name, r.id).bold().magenta()
+        );
+        return Ok(r);
+    }
+    find_reciter(reciters, spec)
+}
+
+fn find_reciter<'a>(reciters: &'a [lookup::Reciter], spec: &str) -> Result<&'a lookup::Reciter> {
+    // config.toml aliases come before any name matching
+    if let Some(id) = config::get().reciter_aliases.as_ref().and_then(|a| lookup::reciter_alias(a, spec)) {
+        return lookup::resolve_reciter(reciters, &id.to_string())
+            .with_context(|| format!("{} {} = {}", "Reciter alias points at no reciter:".red().bold(), spec.bold(), id));
+    }
+    match lookup::resolve_reciter_fuzzy(reciters, spec) {
+        Ok(r) => Ok(r),
+        Err(near) if near.is_empty() => anyhow::bail!("{} {}", "Unknown reciter:".red().bold(), spec.bold()),
+        Err(near) => {
+            let near: Vec<String> = near.iter()
+                .map(|r| match &r.style {
+                    Some(st) => format!("{} ({}, {})", r.reciter_name, r.id, st),
+                    None => format!("{} ({})", r.reciter_name, r.id),
+                })
+                .collect();
+            anyhow::bail!("{} {} — did you mean: {}?", "Unknown reciter:".red().bold(), spec.bold(), near.join(", ").bold())
+        }
+    }
+}
+
 // ---------- main ----------
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -92,132 +809,828 @@ async fn main() -> Result<()> {
         }
     }
 
-    let cli = Cli::parse();
-    let client = Client::new();
+    let cfg = config::load()?;
+    let matches = config::apply(Cli::command(), cfg).get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let res = run(cli).await;
+    if let Err(e) = &res {
+        if let Some(rl) = find_cause::<api::RateLimited>(e) {
+            eprintln!("{} {}", "⏸ Stopped:".yellow().bold(), rl);
+            std::process::exit(75); // EX_TEMPFAIL
+        }
+        if let Some(d) = find_cause::<download::DeadlineHit>(e) {
+            eprintln!("{} {}", "⏱ Stopped:".yellow().bold(), d);
+            std::process::exit(124); // same as timeout(1)
+        }
+    }
+    res
+}
+
+// `e` itself, or what a library HifzrError::Other wraps
+fn find_cause<T: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static>(e: &anyhow::Error) -> Option<&T> {
+    e.downcast_ref::<T>().or_else(|| match e.downcast_ref::<HifzrError>() {
+        Some(HifzrError::Other(inner)) => inner.downcast_ref::<T>(),
+        _ => None,
+    })
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    if cli.refresh {
+        lookup::refresh_cache();
+    }
+    NO_ARABIC.store(cli.no_arabic, std::sync::atomic::Ordering::Relaxed);
+    hifz::set_name_template(cli.name_template.clone());
+    if let Some(p) = &cli.ffmpeg { ffmpeg::set_ffmpeg(PathBuf::from(expand_tilde(p))); }
+    // metadata JSON compresses well; reqwest decodes transparently
+    let client = api::ClientConfig {
+        proxy: cli.proxy.clone(),
+        timeout: std::time::Duration::from_secs(cli.timeout),
+        ..Default::default()
+    }.build()?;
+    // shared download policy; commands fill in force/only_verses
+    if let Some(max) = cli.max_jobs && cli.min_jobs > max {
+        anyhow::bail!("{} {} > {}", "--min-jobs is above --max-jobs:".red().bold(), cli.min_jobs, max);
+    }
+    let fetch = download::FilterOpts {
+        retry: api::RetryConfig {
+            stop_on_429: cli.stop_on_429,
+            max_pages: cli.max_pages,
+            max_retries: cli.max_retries,
+            base_backoff: std::time::Duration::from_millis(cli.retry_backoff_ms),
+            progress: Some(progress().clone()),
+        },
+        deadline: cli.deadline.map(|s| tokio::time::Instant::now() + std::time::Duration::from_secs(s)),
+        max_file_size: Some(cli.max_file_size),
+        max_rate: (cli.max_rate > 0).then(|| std::sync::Arc::new(download::RateLimiter::new(cli.max_rate.saturating_mul(1024)))),
+        adaptive_jobs: cli.max_jobs.map(|max| std::sync::Arc::new(download::AdaptiveJobs::new(cli.min_jobs as usize, max as usize))),
+        resume_partial: cli.resume_partial,
+        progress: Some(progress().clone()),
+        ..Default::default()
+    };
 
     match cli.cmd {
-        Cmd::Download { reciter, chapter, out, force } => {
-            let chapters = lookup::fetch_chapters(&client).await?;
-            let c = lookup::resolve_chapter(&chapters, &chapter)
-                .with_context(|| format!("{} {}", "Unknown chapter:".red().bold(), chapter.bold()))?;
-            let surah_slug = lookup::chapter_slug(c);
-            let surah_display = &c.name_complex;
+        Cmd::Download { reciter, chapter: None, collection: Some(col), name, out, force, index, verify_segments, require_segments, jobs, keep_going, notify, count, dry_run, estimate, file_progress, words, phone_copy, convert, delete_source, loudness, window, tafsir, translation, pick, .. } => {
+            if window.is_set() {
+                anyhow::bail!("{}", "--offset and --limit work on a surah, not a --collection".red().bold());
+            }
+            let reciters = lookup::fetch_reciters(&client, &fetch.retry).await?;
+            let r = pick_reciter(&reciters, &reciter, &pick)?;
+            let folder = lookup::slugify(name.as_deref().unwrap_or(&col));
+            let out_root = per_surah_base(&out, &folder, &lookup::slugify(&r.reciter_name));
+
+            println!("{} {} {}", "".bright_black(), label("Downloading →"), col.bold().cyan());
+            println!("   {} {}", label("Reciter:"), r.reciter_name.bold().magenta());
+            println!("   {} {}", label("Folder:"), out_root.bold().blue());
+
+            let fo = download::FilterOpts {
+                force, verify_segments, require_segments, keep_going, tafsir, translation, dry_run, estimate, file_progress, words,
+                jobs: jobs as usize,
+                phone_copy: phone_copy.as_deref().map(|p| PathBuf::from(per_surah_base(p, &folder, &lookup::slugify(&r.reciter_name)))),
+                convert: convert.map(ConvertArg::codec),
+                delete_source,
+                normalize: loudness.target()?,
+                // not written into collection files, but manifest.json records the name
+                tags: Some(download::TagInfo { surah: col.clone(), reciter: r.reciter_name.clone() }),
+                ..fetch.clone()
+            };
+            if count {
+                let n = download::count_source(&client, r.id, download::Source::Collection(&col), &fo).await?;
+                print_download_count(&[n]);
+                return Ok(());
+            }
+            download::run_source(&client, r.id, download::Source::Collection(&col), &out_root, &fo).await?;
+            if dry_run {
+                return Ok(());
+            }
+
+            println!("{} {} {}", "✔".green().bold(), "Saved under".bold(), out_root.bold().blue());
+            if notify {
+                let n = std::fs::read_dir(&out_root).map(|d| d.flatten()
+                    .filter(|e| e.path().extension().and_then(|x| x.to_str()).is_some_and(|x| hifz::AUDIO_EXTS.contains(&x))).count()).unwrap_or(0);
+                desktop_notify(&format!("Downloaded {} for {}: {} files", col, r.reciter_name, n));
+            }
+            if index {
+                let chapters = lookup::fetch_chapters(&client, &fetch.retry).await.unwrap_or_default();
+                let path = library::write_index(&PathBuf::from(expand_tilde(&out)), &chapters)?;
+                println!("   {} {}", label("Index:"), path.display().bold().blue());
+            }
+        }
 
-            let reciters = lookup::fetch_reciters(&client).await?;
-            let r = lookup::resolve_reciter(&reciters, &reciter)
-                .with_context(|| format!("{} {}", "Unknown reciter:".red().bold(), reciter.bold()))?;
+        Cmd::Download { reciter, chapter, reference, chapter_range, out, force, index, write_info, verify_segments, require_segments, jobs, keep_going, notify, prefetch, count, dry_run, estimate, file_progress, words, phone_copy, convert, delete_source, loudness, window, tafsir, translation, pick, .. } => {
+            // "juz:30" isn't an ayah reference
+            let juz = chapter.as_deref().and_then(|c| c.strip_prefix("juz:"))
+                .map(|n| n.trim().parse::<u32>().ok().filter(|n| (1..=30).contains(n))
+                    .with_context(|| format!("{} {} (expected juz:1 to juz:30)", "Bad --chapter:".red().bold(), n)))
+                .transpose()?;
+            let aref = if juz.is_some() { None } else { ayah_ref(reference.as_deref(), chapter.as_deref())? };
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            let reciters = lookup::fetch_reciters(&client, &fetch.retry).await?;
+            let r = pick_reciter(&reciters, &reciter, &pick)?;
             let rslug = lookup::slugify(&r.reciter_name);
 
-            let out_root = per_surah_base(&out, &surah_slug, &rslug);
+            // each surah with the ayahs to fetch (None: all of them)
+            let mut todo: Vec<(&lookup::Chapter, Option<Vec<u32>>)> = match (aref, juz, chapter_range) {
+                // "2:255-257": that surah, only those ayahs
+                (Some((c, v)), ..) => {
+                    let ch = find_chapter(&chapters, c)?;
+                    let only = hifz::parse_verses_spec(v, ch.verses_count)?;
+                    check_in_surah(ch, &only)?;
+                    vec![(ch, Some(only))]
+                }
+                (_, Some(n), _) => juz_surahs(&client, r.id, n, &fetch).await?.into_iter()
+                    .map(|(id, ayahs)| Ok((find_chapter(&chapters, &id.to_string())?, Some(ayahs))))
+                    .collect::<Result<_>>()?,
+                (_, _, Some((a, b))) => (a..=b)
+                    .map(|id| lookup::resolve_chapter(&chapters, &id.to_string())
+                        .map(|c| (c, None))
+                        .with_context(|| format!("{} {}", "Unknown chapter:".red().bold(), id)))
+                    .collect::<Result<_>>()?,
+                _ => match chapter.as_deref() {
+                    Some(c) if c.eq_ignore_ascii_case("all") => chapters.iter().map(|c| (c, None)).collect(),
+                    // clap guarantees --chapter when there's no --collection/--chapter-range
+                    c => vec![(find_chapter(&chapters, c.unwrap_or_default())?, None)],
+                },
+            };
+            // --offset/--limit: a slice of the one surah's ayahs
+            if window.is_set() {
+                let [(c, only)] = todo.as_mut_slice() else {
+                    anyhow::bail!("{}", "--offset and --limit work on a single surah".red().bold());
+                };
+                let ayahs = match (only.take(), c.verses_count) {
+                    (Some(list), _) => list,
+                    (None, Some(n)) => (1..=n).collect(),
+                    (None, None) => anyhow::bail!("{} {}", "Ayah count unknown for".red().bold(),
+                        format!("{}; give the ayahs in --chapter (e.g. \"{}:1-50\")", c.name_simple, c.id).red().bold()),
+                };
+                *only = Some(window.apply(ayahs, false)?);
+            }
+            let with_only = |only: &Option<Vec<u32>>| download::FilterOpts {
+                only_verses: only.clone().or_else(|| fetch.only_verses.clone()),
+                ..fetch.clone()
+            };
 
-            println!(
-                "{} {} {} {}",
-                "".bright_black(),
-                label("Downloading →"),
-                format!("{}", surah_display).bold().cyan(),
-                format!("({:03} · {})", c.id, c.name_simple).dimmed()
-            );
-            println!(
-                "   {} {}",
-                label("Reciter:"),
-                format!("{}", r.reciter_name).bold().magenta()
-            );
-            println!(
-                "   {} {}",
-                label("Folder:"),
-                out_root.to_string().bold().blue()
-            );
+            if count {
+                let mut counts = Vec::new();
+                for (c, only) in &todo {
+                    counts.push(download::count_source(&client, r.id, download::Source::Chapter(c.id), &with_only(only)).await?);
+                }
+                print_download_count(&counts);
+                return Ok(());
+            }
 
-            download::run_filter(&client, r.id, c.id, &out_root, force, None).await?;
+            let mut failures: Vec<download::SomeFailed> = Vec::new();
+            let mut report = Vec::new();
+            let n = todo.len();
+            // bulk runs resume: a surah with every ayah on disk is done
+            let done = |c: &lookup::Chapter, only: &Option<Vec<u32>>| n > 1 && !force
+                && surah_complete(&per_surah_base(&out, &lookup::chapter_slug(c), &rslug), c, only.as_deref());
+            let mut listings = prefetch.filter(|_| n > 1).map(|ahead| {
+                let ids = todo.iter().filter(|(c, only)| !done(c, only)).map(|(c, _)| c.id).collect();
+                download::prefetch_chapters(&client, r.id, ids, &download::FilterOpts { translation, ..fetch.clone() }, ahead as usize)
+            });
+            for (i, (c, only)) in todo.iter().enumerate() {
+                let c = *c;
+                let surah_slug = lookup::chapter_slug(c);
+                let surah_display = &c.name_complex;
+                let out_root = per_surah_base(&out, &surah_slug, &rslug);
+                let step = if n > 1 { format!("[{}/{}]", i + 1, n) } else { String::new() };
 
-            println!(
-                "{} {} {}",
-                "✔".green().bold(),
-                "Saved under".bold(),
-                out_root.bold().blue()
-            );
-        }
+                if done(c, only) {
+                    println!("{} {} {} {}", step.bright_black(), "✔".green().bold(),
+                        c.name_simple.bold(), "already complete, skipping".dimmed());
+                    continue;
+                }
+                let verses = match &mut listings {
+                    Some(rx) => rx.recv().await.flatten(),
+                    None => None,
+                };
 
-        Cmd::Hifz { chapter, verses, auto_download, reciter, force, repeat, gap_ms, out } => {
-            let chapters = lookup::fetch_chapters(&client).await?;
-            let c = lookup::resolve_chapter(&chapters, &chapter)
-                .with_context(|| format!("{} {}", "Unknown chapter:".red().bold(), chapter.bold()))?;
-            let surah_slug = lookup::chapter_slug(c);
-            let surah_display = &c.name_complex;
-
-            // where we write/read files
-            let out_base = if auto_download || reciter.is_some() {
-                let rec = reciter.as_deref()
-                    .ok_or_else(|| anyhow::anyhow!("{}",
-                        "--reciter is required with --auto-download".yellow().bold()))?;
-                let reciters = lookup::fetch_reciters(&client).await?;
-                let r = lookup::resolve_reciter(&reciters, rec)
-                    .with_context(|| format!("{} {}", "Unknown reciter:".red().bold(), rec.bold()))?;
-                let rslug = lookup::slugify(&r.reciter_name);
                 println!(
-                    "{} {} {}",
-                    "".bright_black(),
-                    label("Auto-download for"),
+                    "{} {} {}{} {}",
+                    step.bright_black(),
+                    label("Downloading →"),
+                    surah_display.bold().cyan(),
+                    arabic_name(c).cyan(),
+                    format!("({:03} · {})", c.id, c.name_simple).dimmed()
+                );
+                println!(
+                    "   {} {}",
+                    label("Reciter:"),
                     r.reciter_name.bold().magenta()
                 );
-                let rec_base = per_surah_base(&out, &surah_slug, &rslug);
+                println!(
+                    "   {} {}",
+                    label("Folder:"),
+                    out_root.to_string().bold().blue()
+                );
 
-                // optional filter list for download
-                let only = verses.as_deref()
-                    .map(|spec| hifz::parse_verses_spec(spec))
-                    .transpose()?.map(|v| v.into_boxed_slice());
-                let only_ref = only.as_deref().map(|b| &b[..]);
+                let fo = download::FilterOpts {
+                    force, verify_segments, require_segments, keep_going, tafsir, translation, dry_run, estimate, file_progress, words,
+                    jobs: jobs as usize,
+                    tags: Some(tag_info(c, r)),
+                    phone_copy: phone_copy.as_deref().map(|p| PathBuf::from(per_surah_base(p, &surah_slug, &rslug))),
+                    convert: convert.map(ConvertArg::codec),
+                    delete_source,
+                    normalize: loudness.target()?,
+                    verses,
+                    ..with_only(only)
+                };
+                match download::run_filter(&client, r.id, c.id, &out_root, &fo).await {
+                    Ok(()) => {}
+                    // --keep-going: carry on with the next surah, report at the end
+                    Err(e) => match e.into_anyhow().downcast::<download::SomeFailed>() {
+                        Ok(f) => {
+                            println!("{} {}", "⚠".yellow().bold(), f.to_string().yellow());
+                            failures.push(f);
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    },
+                }
+                if dry_run {
+                    continue;
+                }
+                if write_info {
+                    write_info_file(&out, c, &r.reciter_name, force);
+                }
 
-                download::run_filter(&client, r.id, c.id, &rec_base, force, only_ref).await?;
-                rec_base
+                println!(
+                    "{} {} {}",
+                    "✔".green().bold(),
+                    "Saved under".bold(),
+                    out_root.bold().blue()
+                );
+                if notify {
+                    let have = hifz::detect_available_ayahs(std::path::Path::new(&out_root)).map(|a| a.len()).unwrap_or(0);
+                    report.push(match c.verses_count {
+                        None => format!("{}: {}", c.name_simple, have),
+                        Some(t) => format!("{}: {}/{}", c.name_simple, have, t),
+                    });
+                }
+            }
+            if notify && !dry_run {
+                let mut msg = match report.as_slice() {
+                    [one] => format!("Downloaded {} for {}", one, r.reciter_name),
+                    many => format!("Downloaded {} surahs for {}", many.len(), r.reciter_name),
+                };
+                if !failures.is_empty() {
+                    msg += &format!(" ({} with failures, see retry.json)", failures.len());
+                }
+                desktop_notify(&msg);
+            }
+            if index && !dry_run {
+                let path = library::write_index(&PathBuf::from(expand_tilde(&out)), &chapters)?;
+                println!("   {} {}", label("Index:"), path.display().bold().blue());
+            }
+            match failures.len() {
+                0 => {}
+                1 => return Err(failures.remove(0).into()),
+                n => anyhow::bail!("{} {} surahs: {}", "Failures in".red().bold(), n,
+                    failures.iter().map(|f| f.retry_file.display().to_string()).collect::<Vec<_>>().join(", ")),
+            }
+        }
+
+        Cmd::Retry { file } => {
+            let file = PathBuf::from(expand_tilde(&file.to_string_lossy()));
+            let (fixed, left) = download::retry_failed(&client, &file, &fetch).await?;
+            println!("{} {} {}", "↻".cyan(), "Retried".bold(), file.display().bold().blue());
+            println!("   {} {}  {} {}", label("Fixed:"), fixed.to_string().green().bold(), label("Still failing:"),
+                if left == 0 { "0".green().bold().to_string() } else { left.to_string().red().bold().to_string() });
+            if left > 0 {
+                anyhow::bail!("{} {}", "Some downloads still fail; see".red().bold(), file.display());
+            }
+        }
+
+        Cmd::Words { dir, force } => {
+            if ffmpeg::detect_ffmpeg().is_none() {
+                return Err(HifzrError::FfmpegMissing.into());
+            }
+            let dir = PathBuf::from(expand_tilde(&dir.to_string_lossy()));
+            let ayahs = hifz::detect_available_ayahs(&dir)
+                .with_context(|| format!("{} {}", "Cannot read".red().bold(), dir.display().bold()))?;
+            let mut clips = 0;
+            for a in ayahs {
+                let mp3 = hifz::ayah_mp3(&dir, a, 0);
+                let stem = mp3.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                if !dir.join(format!("{stem}.segments.json")).exists() {
+                    eprintln!("{} {}", "⚠ no segments.json for".yellow(), stem);
+                    continue;
+                }
+                clips += download::slice_words(&dir, &stem, force)?;
+                eprint!("\rcutting {}: {} clips", stem, clips);
+            }
+            eprintln!();
+            println!("{} {} {}", "✔".green().bold(), format!("{} word clips in", clips).bold(), dir.join("words").display().bold().blue());
+        }
+
+        Cmd::Verify { chapter, reciter, out, repair, jobs } => {
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            let c = find_chapter(&chapters, &chapter)?;
+            let reciters = lookup::fetch_reciters(&client, &fetch.retry).await?;
+            let r = find_reciter(&reciters, &reciter)?;
+            let out_root = per_surah_base(&out, &lookup::chapter_slug(c), &lookup::slugify(&r.reciter_name));
+            // the API may leave verses_count out; count the verses instead
+            let expected = match c.verses_count {
+                Some(n) => n,
+                None => api::fetch_chapter(&client, r.id, c.id, &fetch.retry).await?.len() as u32,
+            };
+
+            if !cli.json {
+                println!("{} {} {} {}", "🩺".cyan(), label("Verify →"), c.name_complex.bold().cyan(),
+                    format!("({:03} · {})", c.id, c.name_simple).dimmed());
+                println!("   {} {}", label("Reciter:"), r.reciter_name.bold().magenta());
+                println!("   {} {}", label("Folder:"), out_root.bold().blue());
+            }
+            let mut report = download::verify_folder(Path::new(&out_root), expected)?;
+            if repair && !report.gaps().is_empty() {
+                if !cli.json { print_verify(&report, false); }
+                let fo = download::FilterOpts {
+                    // a changed file is on disk, so only --force replaces it
+                    force: true,
+                    only_verses: Some(report.gaps()),
+                    jobs: jobs as usize,
+                    tags: Some(tag_info(c, r)),
+                    ..fetch.clone()
+                };
+                eprintln!("{} {} {}", "↻".cyan(), "Repairing".bold(), hifz::format_verses_spec(&report.gaps()).bold());
+                download::run_filter(&client, r.id, c.id, &out_root, &fo).await?;
+                report = download::verify_folder(Path::new(&out_root), expected)?;
+            }
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
             } else {
-                std::path::PathBuf::from(expand_tilde(&out))
-                    .join(&surah_slug)
-                    .to_string_lossy().to_string()
+                print_verify(&report, true);
+            }
+            if !report.gaps().is_empty() {
+                anyhow::bail!("{} {} ayah(s){}", "Incomplete:".red().bold(), report.gaps().len(),
+                    if repair { "" } else { "; --repair fetches them again" });
+            }
+        }
+
+        Cmd::Clean { chapter, reciter, all, out, playlists_only, yes } => {
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            let c = find_chapter(&chapters, &chapter)?;
+            let surah_slug = lookup::chapter_slug(c);
+            // --all wins over a config-file reciter; clap guarantees one of them
+            let dir = match &reciter {
+                Some(spec) if !all => {
+                    let reciters = lookup::fetch_reciters(&client, &fetch.retry).await?;
+                    let r = find_reciter(&reciters, spec)?;
+                    PathBuf::from(per_surah_base(&out, &surah_slug, &lookup::slugify(&r.reciter_name)))
+                }
+                _ => PathBuf::from(expand_tilde(&out)).join(&surah_slug),
             };
+            let targets = library::clean_targets(&dir, playlists_only)?;
+            if targets.is_empty() {
+                println!("{} {}", "✔".green().bold(), format!("Nothing to remove in {}", dir.display()).bold());
+                return Ok(());
+            }
+            let bytes: u64 = targets.iter().map(|t| t.bytes).sum();
+            let size = format!("{:.1} MiB", bytes as f64 / (1 << 20) as f64);
+            println!("{} {} {}", "🧹".cyan(), label("Clean →"), dir.display().bold().blue());
+            for t in &targets {
+                println!("   {} {}", t.path.display(), format!("({} bytes)", t.bytes).dimmed());
+            }
+            if !yes {
+                eprint!("Remove {} item(s), {}? [y/N] ", targets.len(), size);
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                    anyhow::bail!("{}", "Aborted; nothing removed".yellow().bold());
+                }
+            }
+            let freed = library::remove(&targets)?;
+            println!("{} {} {}", "✔".green().bold(), format!("Removed {} item(s), freed", targets.len()).bold(),
+                format!("{} ({} bytes)", size, freed).bold());
+        }
 
-            let m3u = hifz::build_ayah_playlist(&out_base, c.id, verses.as_deref(), repeat, gap_ms)?;
+        Cmd::Hifz { chapter: None, juz: Some(juz), opts, .. } => {
+            juz_playlist(&client, &fetch, juz, &opts, cli.json).await?;
+        }
+
+        Cmd::Hifz { chapter, verses, reference, opts, .. } => {
+            // clap guarantees --chapter or --ref when there's no --juz
+            let (chapter, verses) = match ayah_ref(reference.as_deref(), chapter.as_deref())? {
+                Some((c, v)) if verses.is_none() => (c.to_string(), Some(v.to_string())),
+                Some(_) => anyhow::bail!("{}", "Give the ayahs either in --chapter (\"2:255\") or in --verses, not both".red().bold()),
+                None => (chapter.unwrap_or_default(), verses),
+            };
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            match find_chapters(&chapters, &chapter)?.as_slice() {
+                [c] => hifz_playlist(&client, &fetch, c, verses.as_deref(), &opts, cli.json).await?,
+                several => multi_playlist(&client, &fetch, several, verses.as_deref(), &opts, cli.json).await?,
+            }
+        }
+
+        Cmd::Window { chapter, window, step, session, opts } => {
+            if window == 0 || step == 0 || session == 0 {
+                anyhow::bail!("{}", "--window, --step and --session must all be at least 1".red().bold());
+            }
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            let c = find_chapter(&chapters, &chapter)?;
+
+            let start = (session - 1).saturating_mul(step).saturating_add(1);
+            let mut end = start.saturating_add(window - 1);
+            if let Some(n) = c.verses_count {
+                if start > n {
+                    anyhow::bail!("{} session {} starts at ayah {} but {} has {} ayahs",
+                        "Window past the end:".red().bold(), session, start, c.name_simple, n);
+                }
+                end = end.min(n); // clamp the last windows to the surah end
+            }
+            let spec = format!("{}-{}", start, end);
             println!(
-                "{} {} {}",
-                "📝".yellow(),
-                "Playlist".bold(),
-                m3u.to_string_lossy().bold().blue()
+                "{} {} {} {}",
+                "".bright_black(),
+                label("Window"),
+                format!("#{}", session).bold(),
+                format!("→ ayahs {}", spec).bold().cyan()
             );
+            hifz_playlist(&client, &fetch, c, Some(&spec), &opts, cli.json).await?;
+        }
+
+        Cmd::Probe { path, chapter, reciter, ayah, out } => {
+            let path = match (path, chapter) {
+                (Some(p), _) => PathBuf::from(expand_tilde(&p.to_string_lossy())),
+                (None, Some(chapter)) => {
+                    // clap guarantees reciter + ayah alongside --chapter
+                    let rec = reciter.unwrap_or_default();
+                    let ayah = ayah.unwrap_or_default();
+                    let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+                    let c = find_chapter(&chapters, &chapter)?;
+                    let reciters = lookup::fetch_reciters(&client, &fetch.retry).await?;
+                    let r = find_reciter(&reciters, &rec)?;
+                    let base = per_surah_base(&out, &lookup::chapter_slug(c), &lookup::slugify(&r.reciter_name));
+                    hifz::ayah_mp3(Path::new(&base), ayah, 0)
+                }
+                (None, None) => unreachable!("clap requires a path or --chapter"),
+            };
+
+            let info = probe::probe(&path)
+                .with_context(|| format!("{} {}", "Cannot probe".red().bold(), path.display().bold()))?;
+
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                let unknown = || "unknown".dimmed().to_string();
+                println!("{} {} {}", "🔎".cyan(), "Probe".bold(), info.path.bold().blue());
+                println!(
+                    "   {} {}  {} {}",
+                    label("Size:"),
+                    format!("{} bytes", info.bytes).bold(),
+                    label("Codec:"),
+                    info.codec.as_deref().map(|c| c.bold().to_string()).unwrap_or_else(unknown)
+                );
+                println!(
+                    "   {} {}  {} {}",
+                    label("Duration:"),
+                    info.duration_ms.map(|ms| format!("{:.2} s", ms as f64 / 1000.0).bold().to_string()).unwrap_or_else(unknown),
+                    label("Bitrate:"),
+                    info.bit_rate.map(|b| format!("{} kbps", b / 1000).bold().to_string()).unwrap_or_else(unknown)
+                );
+                println!(
+                    "   {} {}  {} {}",
+                    label("Sample rate:"),
+                    info.sample_rate.map(|r| format!("{} Hz", r).bold().to_string()).unwrap_or_else(unknown),
+                    label("Channels:"),
+                    info.channels.map(|c| c.to_string().bold().to_string()).unwrap_or_else(unknown)
+                );
+                match info.gapless {
+                    Some(g) => println!("   {} {}", label("Gapless:"), format!("delay {} + padding {} samples", g.delay, g.padding).bold()),
+                    None if info.codec.as_deref() == Some("mp3") => println!("   {} {}", label("Gapless:"), "no LAME info frame".dimmed()),
+                    None => {}
+                }
+                if info.looks_like_audio {
+                    println!("   {} {}", label("Audio:"), "looks valid".green().bold());
+                } else {
+                    println!("   {} {}", label("Audio:"), "not an audio file (error page?)".red().bold());
+                }
+                if info.duration_ms.is_none() {
+                    println!("   {}", "hint: install ffprobe (ffmpeg) for duration/bitrate details".yellow());
+                }
+            }
+        }
+
+        Cmd::Silence { gap_ms, out, force } => {
+            if gap_ms == 0 {
+                anyhow::bail!("{}", "--gap-ms must be greater than 0".red().bold());
+            }
+            let dir = PathBuf::from(expand_tilde(&out));
+            std::fs::create_dir_all(&dir)?;
+            let target = hifz::gap_path(&dir, gap_ms, hifz::GapSound::Silence);
+            if force && target.exists() {
+                std::fs::remove_file(&target)?;
+            }
+            let path = hifz::ensure_gap_audio(&dir, gap_ms, hifz::GapSound::Silence, &Some(progress().clone()))
+                .with_context(|| format!("{} {}", "Could not generate".red().bold(),
+                    format!("{} (is ffmpeg installed?)", target.display()).bold()))?;
+            let measured = probe::duration_ms(&path);
+            println!("{} {} {}", "🔇".cyan(), "Silence".bold(), path.display().bold().blue());
             println!(
-                "   {} {} {}",
-                label("Surah:"),
-                format!("{}", surah_display).bold().cyan(),
-                format!("({:03} · {})", c.id, c.name_simple).dimmed()
+                "   {} {}",
+                label("Duration:"),
+                match measured {
+                    Some(ms) => format!("{} ms (asked {} ms)", ms, gap_ms).bold().to_string(),
+                    None => format!("{} ms", gap_ms).bold().to_string(),
+                }
             );
+        }
+
+        Cmd::Index { out } => {
+            let root = PathBuf::from(expand_tilde(&out));
+            if !root.is_dir() {
+                anyhow::bail!("{} {}", "No library at".red().bold(), root.display().bold());
+            }
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await.unwrap_or_default();
+            let path = library::write_index(&root, &chapters)?;
+            println!("{} {} {}", "🗂".cyan(), "Index".bold(), path.display().bold().blue());
+        }
+
+        Cmd::Tree { out } => {
+            let root = PathBuf::from(expand_tilde(&out));
+            if !root.is_dir() {
+                anyhow::bail!("{} {}", "No library at".red().bold(), root.display().bold());
+            }
+            // totals are a nice-to-have; still show counts when offline
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await.unwrap_or_default();
+            let tree = library::scan(&root, &chapters)?;
+
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&tree)?);
+            } else {
+                println!("{} {} {}", "📚".cyan(), "Library".bold(), root.display().bold().blue());
+                for s in &tree {
+                    println!("{}", s.slug.bold().cyan());
+                    for (i, r) in s.reciters.iter().enumerate() {
+                        let branch = if i + 1 == s.reciters.len() { "└─" } else { "├─" };
+                        let have = r.ayahs.len();
+                        let count = match s.total {
+                            Some(t) if have as u32 >= t => format!("{}/{} ayahs", have, t).green().bold().to_string(),
+                            Some(t) => format!("{}/{} ayahs", have, t).yellow().bold().to_string(),
+                            None => format!("{} ayahs", have).bold().to_string(),
+                        };
+                        println!("  {} {}  {}", branch.bright_black(), r.slug.magenta(), count);
+                    }
+                }
+                if tree.is_empty() {
+                    println!("   {}", "nothing downloaded yet".dimmed());
+                }
+            }
+        }
+
+        Cmd::Play { path, chapter, reciter, out, player, repeat } => {
+            let target = match (path, chapter) {
+                (Some(p), _) => PathBuf::from(expand_tilde(&p.to_string_lossy())),
+                (None, Some(chapter)) => {
+                    // clap guarantees --reciter alongside --chapter
+                    let rec = reciter.unwrap_or_default();
+                    let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+                    let c = find_chapter(&chapters, &chapter)?;
+                    let reciters = lookup::fetch_reciters(&client, &fetch.retry).await?;
+                    let r = find_reciter(&reciters, &rec)?;
+                    PathBuf::from(per_surah_base(&out, &lookup::chapter_slug(c), &lookup::slugify(&r.reciter_name)))
+                }
+                (None, None) => PathBuf::from("."),
+            };
+            let playlist = if target.is_dir() {
+                find_playlist(&target)
+                    .with_context(|| format!("{} {} (run `hifzr hifz` first)", "No playlist in".red().bold(), target.display().bold()))?
+            } else if target.is_file() {
+                target
+            } else {
+                anyhow::bail!("{} {}", "No such playlist:".red().bold(), target.display().bold());
+            };
+
+            let candidates: Vec<String> = match player.or_else(|| std::env::var("HIFZR_PLAYER").ok().filter(|p| !p.trim().is_empty())) {
+                Some(p) => vec![p],
+                None => ["mpv", "vlc", "cvlc"].map(String::from).to_vec(),
+            };
+            let Some(exe) = candidates.iter().find(|p| on_path(p)) else {
+                println!("{} {} {}", "▶".cyan(), label("Playlist:"), playlist.display().bold().blue());
+                println!("   {}", format!("no player found (tried {}); pass --player or set HIFZR_PLAYER", candidates.join(", ")).yellow());
+                return Ok(());
+            };
+            let mut cmd = std::process::Command::new(exe);
+            let name = Path::new(exe).file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            if repeat {
+                match name.as_str() {
+                    "mpv" => { cmd.arg("--loop-playlist"); }
+                    "vlc" | "cvlc" => { cmd.arg("--loop"); }
+                    _ => eprintln!("warning: don't know how to make {} loop; playing once", exe),
+                }
+            }
+            println!("{} {} {}", "▶".cyan(), format!("Playing with {}", exe).bold(), playlist.display().bold().blue());
+            let status = cmd.arg(&playlist).status()
+                .with_context(|| format!("{} {}", "Could not start".red().bold(), exe.bold()))?;
+            if !status.success() {
+                anyhow::bail!("{} exited with {}", exe, status);
+            }
+        }
+
+        Cmd::Checklist { chapter, verses, output } => {
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            let c = find_chapter(&chapters, &chapter)?;
+            let ayahs = match verses.as_deref() {
+                Some(spec) => hifz::parse_verses_spec(spec, c.verses_count)?,
+                None => match c.verses_count {
+                    Some(n) => (1..=n).collect(),
+                    None => anyhow::bail!("{}", "Ayah count unknown for this surah; pass --verses".red().bold()),
+                },
+            };
+            if let Some(n) = c.verses_count
+                && let Some(&a) = ayahs.iter().find(|&&a| a > n) {
+                anyhow::bail!("{} {} has {} ayahs, asked for {}", "Out of range:".red().bold(),
+                    c.name_simple.bold(), n, a);
+            }
+            let title = match verses.as_deref() {
+                Some(spec) => format!("{:03} {} ({}) — ayahs {}", c.id, c.name_complex, c.name_simple, spec),
+                None => format!("{:03} {} ({})", c.id, c.name_complex, c.name_simple),
+            };
+            let path = PathBuf::from(expand_tilde(&output));
+            hifz::write_checklist(&path, &title, c.id, &ayahs)?;
+            println!("{} {} {}", "📝".cyan(), "Checklist".bold(), path.display().bold().blue());
+            println!("   {} {}", label("Ayahs:"), ayahs.len().to_string().bold());
+        }
+
+        Cmd::Benchmark { reciter, chapter, pick } => {
+            let chapters = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            let c = find_chapter(&chapters, &chapter)?;
+            let reciters = lookup::fetch_reciters(&client, &fetch.retry).await?;
+            let r = pick_reciter(&reciters, &reciter, &pick)?;
+
+            let tmp = std::env::temp_dir().join(format!("hifzr-bench-{}", std::process::id()));
+            let timings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let fo = download::FilterOpts { force: true, timings: Some(timings.clone()), ..fetch.clone() };
             println!(
-                "   {} {}  {} {}",
-                label("Repeat:"),
-                repeat.to_string().bold(),
-                label("Gap:"),
-                if gap_ms == 0 { "none".bold().to_string() } else { format!("{} ms", gap_ms).bold().to_string() }
+                "{} {} {} {}",
+                "⏱".cyan(),
+                label("Benchmark →"),
+                c.name_complex.bold().cyan(),
+                format!("({})", r.reciter_name).magenta()
             );
+            let started = std::time::Instant::now();
+            let res = download::run_filter(&client, r.id, c.id, &tmp.to_string_lossy(), &fo).await;
+            let wall = started.elapsed();
+            // clean up before reporting so a failed run leaves nothing behind
+            let _ = std::fs::remove_dir_all(&tmp);
+            res?;
+
+            let report = download::BenchReport::new(&timings.lock().unwrap(), wall);
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!(
+                    "   {} {}  {} {}",
+                    label("Files:"),
+                    report.files.to_string().bold(),
+                    label("Size:"),
+                    format!("{:.1} MB", report.bytes as f64 / 1e6).bold()
+                );
+                println!(
+                    "   {} {}",
+                    label("Per file:"),
+                    format!("min {} ms · avg {} ms · max {} ms", report.min_ms, report.avg_ms, report.max_ms).bold()
+                );
+                println!(
+                    "   {} {}",
+                    label("Total:"),
+                    format!("{:.2} MB/s over {:.1}s", report.mb_per_s, wall.as_secs_f64()).bold().green()
+                );
+            }
         }
 
-Cmd::Ls { what } => {
+        Cmd::Doctor => {
+            let tools = [
+                ("ffmpeg", ffmpeg::detect_ffmpeg(), "gap tones, --concat, --convert, --normalize, --words, --phone-copy"),
+                ("ffprobe", ffmpeg::detect_ffprobe(), "lengths: --gap-ratio, --select, --order, #EXTINF, probe"),
+            ];
+            if cli.json {
+                let report: serde_json::Map<String, serde_json::Value> = tools.iter()
+                    .map(|(name, t, _)| (name.to_string(), serde_json::to_value(t).unwrap_or_default()))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            for (name, tool, needed_for) in &tools {
+                match tool {
+                    Some(t) if t.version.is_empty() => println!("{} {} {}", "✔".green().bold(), name.bold(), t.path.display().to_string().blue()),
+                    Some(t) => println!("{} {} {} {}", "✔".green().bold(), name.bold(), t.path.display().to_string().blue(),
+                        format!("({})", t.version).dimmed()),
+                    None => {
+                        println!("{} {} {}", "✘".red().bold(), name.bold(), "not found".red());
+                        println!("   {} {}", label("Needed for:"), needed_for);
+                    }
+                }
+            }
+            if tools.iter().any(|(_, t, _)| t.is_none()) {
+                println!("   {}", "Install ffmpeg (it ships ffprobe too), or point --ffmpeg / HIFZR_FFMPEG at its binary".yellow());
+            }
+        }
+
+        Cmd::Config { what: ConfigCmd::Path } => {
+            let p = config::path().context("no $XDG_CONFIG_HOME or $HOME to put a config file in")?;
+            println!("{}", p.display());
+            if !p.is_file() {
+                eprintln!("{}", "(doesn't exist yet; hifzr runs on built-in defaults)".dimmed());
+            }
+        }
+
+        Cmd::Config { what: ConfigCmd::Dump } => {
+            let cfg = config::get();
+            print!("{}", config::dump(&config::apply(Cli::command(), cfg), cfg, cli.proxy.as_deref())?);
+        }
+
+Cmd::Ls { what, sort, filter } => {
+    // "rahman" finds "Ar-Raḥmān"; an Arabic filter normalizes to nothing, so
+    // that one is matched as typed
+    let raw = filter.as_deref().map(str::trim).unwrap_or_default();
+    let key = lookup::norm_key(raw);
+    let hit = |names: &[&str]| raw.is_empty() || names.iter().any(|n| match key.is_empty() {
+        true => n.contains(raw),
+        false => lookup::norm_key(n).contains(&key),
+    });
+    let none_found = |n: usize| if n == 0 && !raw.is_empty() {
+        println!("   {}", format!("no matches for {:?}", filter.as_deref().unwrap_or_default()).dimmed());
+    };
     match what {
         ListWhat::Chapters => {
-            let ch = lookup::fetch_chapters(&client).await?;
+            let mut ch = lookup::fetch_chapters(&client, &fetch.retry).await?;
+            ch.retain(|c| hit(&[&c.name_simple, &c.name_complex, &c.translated_name.name, &c.name_arabic]));
+            match sort {
+                SortBy::Id => {}
+                SortBy::Name => ch.sort_by_key(|c| lookup::slugify(&c.name_simple)),
+                SortBy::Length => ch.sort_by_key(|c| (c.verses_count, c.id)),
+            }
+            // pad by chars: name_complex carries combining marks
+            let pad = |s: &str, w: usize| format!("{}{}", s, " ".repeat(w.saturating_sub(s.chars().count())));
+            let complex_w = ch.iter().map(|c| c.name_complex.chars().count()).max().unwrap_or(0);
+            let simple_w = ch.iter().map(|c| c.name_simple.chars().count() + 2).max().unwrap_or(0);
             println!("{}", "Chapters".bold().cyan());
+            none_found(ch.len());
             for c in ch {
                 let id_text = format!("{:>3}", c.id);
-                let simple_text = format!("[{}]", c.name_simple);
+                let simple_text = pad(&format!("[{}]", c.name_simple), simple_w);
+                let count_text = match c.verses_count {
+                    Some(n) => format!("{:>3} ayahs", n),
+                    None => " ".repeat(9),
+                };
+                let place_text = match c.revelation_place.as_deref() {
+                    Some("makkah") => "Meccan",
+                    Some("madinah") => "Medinan",
+                    other => other.unwrap_or_default(),
+                };
 
+                // the Arabic name goes last: its width varies between terminals
+                let arabic = arabic_name(&c);
+                let place_text = if arabic.is_empty() { place_text.to_string() } else { pad(place_text, 8) };
                 println!(
-                    "{}  {}  {}",
+                    "{}  {}  {}  {}  {}{}",
                     id_text.magenta().bold(),      // color at the call site
-                    c.name_complex.bold(),         // borrow field directly
+                    pad(&c.name_complex, complex_w).bold(),
                     simple_text.dimmed(),          // color a bound String
+                    count_text.cyan(),
+                    place_text.dimmed(),
+                    arabic,
+                );
+            }
+        }
+        ListWhat::Tafsirs => {
+            let mut ts = lookup::fetch_tafsirs(&client, &fetch.retry).await?;
+            ts.retain(|t| hit(&[&t.name, &t.author_name]));
+            println!("{}", "Tafsirs".bold().cyan());
+            none_found(ts.len());
+            for t in ts {
+                let id_text = format!("{:>3}", t.id);
+                let meta = format!("({}, {})", t.author_name, t.language_name);
+                println!(
+                    "{}  {}  {}",
+                    id_text.magenta().bold(),
+                    t.name.bold(),
+                    meta.dimmed(),
+                );
+            }
+        }
+        ListWhat::Translations => {
+            let mut ts = lookup::fetch_translations(&client, &fetch.retry).await?;
+            ts.retain(|t| hit(&[&t.name, &t.author_name, &t.language_name]));
+            println!("{}", "Translations".bold().cyan());
+            none_found(ts.len());
+            for t in ts {
+                let id_text = format!("{:>3}", t.id);
+                let meta = format!("({}, {})", t.author_name, t.language_name);
+                println!(
+                    "{}  {}  {}",
+                    id_text.magenta().bold(),
+                    t.name.bold(),
+                    meta.dimmed(),
                 );
             }
         }
         ListWhat::Reciters => {
-            let rs = lookup::fetch_reciters(&client).await?;
+            let mut rs = lookup::fetch_reciters(&client, &fetch.retry).await?;
+            rs.retain(|r| hit(&[&r.reciter_name, r.style.as_deref().unwrap_or_default()]));
             println!("{}", "Reciters".bold().magenta());
+            none_found(rs.len());
             for r in rs {
                 let id_text = format!("{:>3}", r.id);
                 if let Some(style) = r.style.as_deref().filter(|s| !s.is_empty()) {
@@ -244,3 +1657,676 @@ Cmd::Ls { what } => {
 
     Ok(())
 }
+
+// Validate the shared playlist flags and turn them into builder options
+fn playlist_opts(opts: &HifzOpts) -> Result<hifz::PlaylistOpts> {
+    if let Some(r) = opts.gap_ratio
+        && !(r > 0.0 && r.is_finite()) {
+        anyhow::bail!("{} {}", "--gap-ratio must be a positive number, got".red().bold(), r);
+    }
+    if opts.path_prefix.as_deref().is_some_and(|p| p.trim().is_empty()) {
+        anyhow::bail!("{}", "--path-prefix must not be empty".red().bold());
+    }
+    if opts.gap_sound == GapKind::Tone && !(20..=20_000).contains(&opts.tone_hz) {
+        anyhow::bail!("{} {}", "--tone-hz must be between 20 and 20000, got".red().bold(), opts.tone_hz);
+    }
+    // a .pls carries its entry count up front, so it can't just grow at the end
+    if opts.append_new && opts.format == FormatArg::Pls {
+        anyhow::bail!("{}", "--append-new works with m3u/m3u8 playlists, not pls".red().bold());
+    }
+    Ok(hifz::PlaylistOpts {
+        repeat: opts.repeat(),
+        repeat_for: opts.repeat.as_ref().map(|r| r.per_ayah.clone()).unwrap_or_default(),
+        cumulative: opts.cumulative,
+        gap_ms: opts.gap_ms(),
+        between_ayah_gap_ms: opts.between_ayah_gap,
+        gap_ratio: opts.gap_ratio,
+        path_prefix: opts.path_prefix.clone().map(|prefix| hifz::PathPrefix {
+            root: PathBuf::from(expand_tilde(&opts.out)),
+            prefix,
+        }),
+        gap_sound: match opts.gap_sound {
+            GapKind::Silence => hifz::GapSound::Silence,
+            GapKind::Tone => hifz::GapSound::Tone(opts.tone_hz),
+        },
+        order: match opts.order {
+            OrderArg::Ayah => hifz::Order::Ayah,
+            OrderArg::DurationAsc => hifz::Order::DurationAsc,
+            OrderArg::DurationDesc => hifz::Order::DurationDesc,
+        },
+        omit_gap_files: opts.no_silence_file_in_playlist,
+        playlist_dir: opts.playlist_dir.as_deref().map(|d| PathBuf::from(expand_tilde(d))),
+        stem_shift: 1 - opts.ayah_numbers_from as i32,
+        require_segments: opts.require_segments,
+        format: match opts.format {
+            FormatArg::M3u => hifz::PlaylistFormat::M3u,
+            FormatArg::Pls => hifz::PlaylistFormat::Pls,
+            FormatArg::ExtM3u => hifz::PlaylistFormat::ExtM3u,
+        },
+        ayah_count: None, // known once the surah is
+        interleave: None, // the second reciter's folder, once resolved
+        repeat_b: opts.repeat_b,
+        // a fresh seed is printed with the playlist, so a good drill can be replayed
+        shuffle: opts.shuffle.then(|| hifz::Shuffle {
+            seed: opts.pick.seed.unwrap_or_else(rand::random),
+            repeats: opts.shuffle_repeats,
+        }),
+        progress: Some(progress().clone()),
+    })
+}
+
+// "12 ayahs × 3 = 36 plays"; just the total when the repeats vary
+fn plays_text(ayahs: usize, plays: usize, popts: &hifz::PlaylistOpts) -> String {
+    if popts.repeat_for.is_empty() && !popts.cumulative && popts.interleave.is_none() {
+        format!("{} ayahs × {} = {} plays", ayahs, popts.repeat, plays)
+    } else {
+        format!("{} ayahs → {} plays", ayahs, plays)
+    }
+}
+
+fn print_repeat_gap(popts: &hifz::PlaylistOpts) {
+    let mut repeat = popts.repeat.to_string();
+    if !popts.repeat_for.is_empty() {
+        let each: Vec<String> = popts.repeat_for.iter().map(|(a, n)| format!("{a}:{n}")).collect();
+        repeat += &format!(" ({})", each.join(", "));
+    }
+    if popts.cumulative { repeat += ", cumulative"; }
+    if popts.interleave.is_some() {
+        repeat += &format!(", then {} from the second reciter", popts.repeat_b.map_or("the same".to_string(), |n| n.to_string()));
+    }
+    println!(
+        "   {} {}  {} {}",
+        label("Repeat:"),
+        repeat.bold(),
+        label("Gap:"),
+        match popts.gap_ratio {
+            Some(r) if popts.gap_ms == 0 => format!("{:.0}% of ayah", r * 100.0).bold().to_string(),
+            Some(r) => format!("{:.0}% of ayah (else {} ms)", r * 100.0, popts.gap_ms).bold().to_string(),
+            None if popts.gap_ms == 0 => "none".bold().to_string(),
+            None => format!("{} ms", popts.gap_ms).bold().to_string(),
+        }
+    );
+    if let Some(ms) = popts.between_ayah_gap_ms {
+        println!("   {} {}", label("Between ayahs:"), format!("{} ms", ms).bold());
+    }
+    if let Some(s) = popts.shuffle {
+        let what = if s.repeats { "ayahs and repeats" } else { "ayahs" };
+        println!("   {} {} {}", label("Shuffled:"), what.bold(), format!("(--seed {})", s.seed).dimmed());
+    }
+}
+
+// Resolve the folder (auto-downloading if asked), write the playlist, report
+async fn hifz_playlist(
+    client: &Client,
+    fetch: &download::FilterOpts,
+    c: &lookup::Chapter,
+    verses: Option<&str>,
+    opts: &HifzOpts,
+    json: bool,
+) -> Result<()> {
+    let ayah_count = c.verses_count;
+    if let Some(spec) = verses
+        && let Some(count) = ayah_count
+        && let Some(&last) = hifz::parse_verses_spec(spec, ayah_count)?.iter().max()
+        && last > count {
+        anyhow::bail!("{} {} has {} ayahs, but --verses asks for {}",
+            "Out of range:".red().bold(), c.name_simple, count, last);
+    }
+    let surah_slug = lookup::chapter_slug(c);
+    let surah_display = &c.name_complex;
+    let mut popts = hifz::PlaylistOpts { ayah_count, ..playlist_opts(opts)? };
+    let downloading = opts.auto_download || opts.reciter.is_some() || opts.interleave;
+
+    // --offset/--limit cut the spec (or the whole surah, when downloading)
+    // before anything is fetched; otherwise they wait for the folder scan
+    let windowed;
+    let verses = match verses {
+        Some(spec) if opts.window.is_set() => {
+            windowed = opts.window.apply_spec(spec, ayah_count, json)?;
+            Some(windowed.as_str())
+        }
+        None if opts.window.is_set() && downloading && ayah_count.is_some() => {
+            let all = (1..=ayah_count.unwrap_or_default()).collect();
+            windowed = hifz::format_verses_spec(&opts.window.apply(all, json)?);
+            Some(windowed.as_str())
+        }
+        other => other,
+    };
+
+    // where we write/read files
+    let out_base = if downloading {
+        let rec = opts.reciter.as_deref()
+            .ok_or_else(|| anyhow::anyhow!("{}",
+                "--reciter is required with --auto-download and --interleave".yellow().bold()))?;
+        let reciters = lookup::fetch_reciters(client, &fetch.retry).await?;
+        let r = pick_reciter(&reciters, rec, &opts.pick)?;
+        let rslug = lookup::slugify(&r.reciter_name);
+        println!(
+            "{} {} {}",
+            "".bright_black(),
+            label("Auto-download for"),
+            r.reciter_name.bold().magenta()
+        );
+        let rec_base = per_surah_base(&opts.out, &surah_slug, &rslug);
+        // --interleave: the second reciter's folder, fetched the same way
+        let partner = match opts.reciter_b.as_deref() {
+            Some(spec) => {
+                let b = pick_reciter(&reciters, spec, &opts.pick)?;
+                println!("   {} {}", label("Interleaved with"), b.reciter_name.bold().magenta());
+                let b_base = per_surah_base(&opts.out, &surah_slug, &lookup::slugify(&b.reciter_name));
+                popts.interleave = Some(PathBuf::from(&b_base));
+                Some((b, b_base))
+            }
+            None => None,
+        };
+        if opts.dry_run || opts.count || opts.print_spec {
+            let why = if opts.dry_run { "dry run" } else { "report only" };
+            println!("   {}", format!("{}: skipping download", why).dimmed());
+        } else {
+            // optional filter list for download
+            let only_verses = verses
+                .map(|spec| hifz::parse_verses_spec(spec, ayah_count))
+                .transpose()?;
+
+            let fo = download::FilterOpts {
+                force: opts.force, only_verses, require_segments: opts.require_segments, jobs: opts.jobs as usize,
+                tags: Some(tag_info(c, r)), normalize: opts.loudness.target()?, ..fetch.clone()
+            };
+            download::run_filter(client, r.id, c.id, &rec_base, &fo).await?;
+            if let Some((b, b_base)) = &partner {
+                let fo = download::FilterOpts { tags: Some(tag_info(c, b)), ..fo };
+                download::run_filter(client, b.id, c.id, b_base, &fo).await?;
+            }
+            if opts.write_info {
+                write_info_file(&opts.out, c, &r.reciter_name, opts.force);
+            }
+        }
+        rec_base
+    } else {
+        std::path::PathBuf::from(expand_tilde(&opts.out))
+            .join(&surah_slug)
+            .to_string_lossy().to_string()
+    };
+
+    // an empty folder is usually a naming mismatch, not a missing download
+    let base_path = PathBuf::from(&out_base);
+    let has_ayahs = base_path.is_dir()
+        && !hifz::detect_ayahs_shifted(&base_path, popts.stem_shift)?.is_empty();
+    if !has_ayahs {
+        let (hint, others) = if opts.reciter.is_some() {
+            ("did the reciter name change?", library::similar_siblings(&base_path))
+        } else {
+            ("pass --reciter to use them", library::reciter_dirs_with_ayahs(&base_path))
+        };
+        for (slug, n) in others {
+            println!(
+                "{} {} {} {}",
+                "⚠".yellow().bold(),
+                format!("no ayahs in {}, but found {} under", base_path.display(), n).yellow(),
+                slug.bold().magenta(),
+                format!("— {}", hint).yellow()
+            );
+        }
+    }
+
+    let scanned;
+    let verses = match verses {
+        None if opts.window.is_set() && base_path.is_dir() => {
+            let found = hifz::detect_ayahs_shifted(&base_path, popts.stem_shift)?;
+            scanned = hifz::format_verses_spec(&opts.window.apply(found, json)?);
+            Some(scanned.as_str())
+        }
+        other => other,
+    };
+
+    // --playlist-only-existing: intersect the spec with what's on disk
+    let existing;
+    let verses = match verses {
+        Some(spec) if opts.playlist_only_existing => {
+            let (have, missing) = hifz::split_existing(&out_base, spec, popts.stem_shift, ayah_count)?;
+            println!(
+                "{} {} {}",
+                "".bright_black(),
+                label("Existing:"),
+                format!("{} of {} requested ayahs", have.len(), have.len() + missing.len()).bold()
+            );
+            if !opts.dry_run
+                && let Some(path) = hifz::write_missing_list(&out_base, &missing, popts.stem_shift)? {
+                println!("   {} {}", label("Missing list:"), path.display().bold().yellow());
+            }
+            if have.is_empty() {
+                anyhow::bail!("{} {}", "None of the requested ayahs are in".red().bold(), out_base.bold());
+            }
+            existing = have.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+            Some(existing.as_str())
+        }
+        other => other,
+    };
+
+    // --select narrows the candidates down to a comma list for the builder
+    let selected;
+    let verses = match opts.select {
+        Some(sel) => {
+            let picked = hifz::select_by_duration(&out_base, verses, sel, popts.stem_shift, ayah_count)?;
+            println!("{} {} {}", "".bright_black(), label("Selected"), sel.to_string().bold());
+            for (ayah, ms) in &picked {
+                println!(
+                    "   {} {}",
+                    format!("{:03}", ayah).bold().cyan(),
+                    format!("{:.1} s", *ms as f64 / 1000.0).dimmed()
+                );
+            }
+            let mut ayahs: Vec<u32> = picked.iter().map(|(a, _)| *a).collect();
+            ayahs.sort_unstable();
+            selected = ayahs.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+            Some(selected.as_str())
+        }
+        None => verses,
+    };
+
+    if opts.print_spec {
+        let list = match verses {
+            Some(spec) => hifz::parse_verses_spec(spec, ayah_count)?,
+            None if base_path.is_dir() => hifz::detect_ayahs_shifted(&base_path, popts.stem_shift)?,
+            None => Vec::new(),
+        };
+        println!("{}", hifz::format_verses_spec(&list));
+        return Ok(());
+    }
+    if opts.count {
+        return print_playlist_count(&out_base, verses, &popts, json);
+    }
+    if opts.dry_run {
+        return print_playlist_plan(&out_base, verses, &popts, json);
+    }
+
+    if popts.order != hifz::Order::Ayah {
+        let dir = PathBuf::from(&out_base);
+        let list = match verses {
+            Some(spec) => hifz::parse_verses_spec(spec, ayah_count)?,
+            None => hifz::detect_ayahs_shifted(&dir, popts.stem_shift)?,
+        };
+        let ordered = hifz::order_ayahs(&dir, list, popts.order, popts.stem_shift);
+        let shown: Vec<String> = ordered.iter()
+            .filter(|(a, _)| hifz::ayah_mp3(&dir, *a, popts.stem_shift).exists())
+            .map(|(a, ms)| match ms {
+                Some(ms) => format!("{:03} ({:.1}s)", a, *ms as f64 / 1000.0),
+                None => format!("{:03} (?)", a),
+            })
+            .collect();
+        println!("{} {} {}", "".bright_black(), label("Order:"), shown.join(" ").bold());
+    }
+
+    let m3u = if opts.append_new {
+        let (m3u, added) = hifz::append_new_ayahs(&out_base, &c.name_simple, verses, &popts)?;
+        let list = if added.is_empty() {
+            "nothing new".dimmed().to_string()
+        } else {
+            added.iter().map(|a| format!("{:03}", a)).collect::<Vec<_>>().join(" ").bold().to_string()
+        };
+        println!("{} {} {}", "".bright_black(), label("Appended:"), list);
+        m3u
+    } else {
+        hifz::build_ayah_playlist(&out_base, &c.name_simple, verses, &popts)?
+    };
+    println!(
+        "{} {} {}",
+        "📝".yellow(),
+        "Playlist".bold(),
+        m3u.to_string_lossy().bold().blue()
+    );
+    println!(
+        "   {} {}{} {}",
+        label("Surah:"),
+        surah_display.bold().cyan(),
+        arabic_name(c).cyan(),
+        format!("({:03} · {})", c.id, c.name_simple).dimmed()
+    );
+    print_repeat_gap(&popts);
+
+    if opts.concat {
+        let mp3 = hifz::concat_playlist(&out_base, verses, &popts, opts.gapless)
+            .with_context(|| format!("{}", "Could not build the single mp3".red().bold()))?;
+        if let Some(lufs) = opts.loudness.target()? {
+            download::normalize_loudness(&mp3, lufs)
+                .with_context(|| format!("{}", "Could not normalize the single mp3".red().bold()))?;
+        }
+        println!("{} {} {}", "🎧".yellow(), "Single file".bold(), mp3.display().bold().blue());
+        let cue = mp3.with_extension("cue");
+        if cue.is_file() { println!("   {} {}", label("Cue sheet:"), cue.display().to_string().blue()); }
+    }
+    Ok(())
+}
+
+// Surah id → ayahs of that surah in the juz. The boundaries come from the
+// API's verse listing; the first and last surah are often partial
+async fn juz_surahs(
+    client: &Client,
+    audio: u32,
+    juz: u32,
+    fetch: &download::FilterOpts,
+) -> Result<std::collections::BTreeMap<u32, Vec<u32>>> {
+    let verses = api::fetch_collection(client, audio, &format!("verses/by_juz/{}", juz), &fetch.retry).await
+        .with_context(|| format!("{} {}", "Could not list juz".red().bold(), juz))?;
+    let mut by_surah: std::collections::BTreeMap<u32, Vec<u32>> = Default::default();
+    for v in &verses {
+        let surah = v.verse_key.split_once(':').and_then(|(s, _)| s.parse().ok())
+            .with_context(|| format!("odd verse key {:?}", v.verse_key))?;
+        by_surah.entry(surah).or_default().push(v.verse_number);
+    }
+    Ok(by_surah)
+}
+
+// `hifz --juz N`: download each surah's slice of the juz into its usual
+// folder, then write one playlist over all of them in mushaf order
+async fn juz_playlist(
+    client: &Client,
+    fetch: &download::FilterOpts,
+    juz: u32,
+    opts: &HifzOpts,
+    json: bool,
+) -> Result<()> {
+    check_combined(opts, "--juz")?;
+    let popts = playlist_opts(opts)?;
+    // clap guarantees --reciter with --juz
+    let rec = opts.reciter.as_deref().unwrap_or_default();
+    let reciters = lookup::fetch_reciters(client, &fetch.retry).await?;
+    let r = pick_reciter(&reciters, rec, &opts.pick)?;
+    let rslug = lookup::slugify(&r.reciter_name);
+    let chapters = lookup::fetch_chapters(client, &fetch.retry).await?;
+
+    let by_surah = juz_surahs(client, r.id, juz, fetch).await?;
+    let total: usize = by_surah.values().map(Vec::len).sum();
+    let plays: usize = by_surah.values().map(|a| popts.plays(a)).sum();
+
+    if opts.print_spec {
+        for (id, ayahs) in &by_surah {
+            println!("{}:{}", id, hifz::format_verses_spec(ayahs));
+        }
+        return Ok(());
+    }
+
+    println!(
+        "{} {} {} {}",
+        "".bright_black(),
+        label("Juz"),
+        juz.to_string().bold().cyan(),
+        format!("→ {} ayahs in {} surahs", total, by_surah.len()).dimmed()
+    );
+
+    if opts.count {
+        println!(
+            "{} {} {}",
+            "#".cyan(),
+            label("Count:"),
+            plays_text(total, plays, &popts).bold()
+        );
+        return Ok(());
+    }
+
+    let mut parts = Vec::new();
+    for (id, ayahs) in by_surah {
+        let c = chapters.iter().find(|c| c.id == id)
+            .with_context(|| format!("{} {}", "Unknown chapter:".red().bold(), id))?;
+        let base = per_surah_base(&opts.out, &lookup::chapter_slug(c), &rslug);
+        let spec = format!("{}-{}", ayahs.first().unwrap_or(&1), ayahs.last().unwrap_or(&1));
+        println!("   {} {}", format!("{:03} {}", c.id, c.name_simple).bold(), spec.dimmed());
+        if !opts.dry_run {
+            let fo = download::FilterOpts { force: opts.force, only_verses: Some(ayahs), require_segments: opts.require_segments, jobs: opts.jobs as usize, tags: Some(tag_info(c, r)), normalize: opts.loudness.target()?, ..fetch.clone() };
+            download::run_filter(client, r.id, c.id, &base, &fo).await?;
+            if opts.write_info {
+                write_info_file(&opts.out, c, &r.reciter_name, opts.force);
+            }
+        }
+        parts.push((base, Some(spec), c.name_simple.clone()));
+    }
+
+    let target = PathBuf::from(per_surah_base(&opts.out, &format!("juz-{:03}", juz), &rslug));
+    write_combined(&target, &parts, &popts, r, opts.dry_run, json)
+}
+
+// Options that only make sense within one surah's folder
+fn check_combined(opts: &HifzOpts, what: &str) -> Result<()> {
+    if opts.select.is_some() || opts.append_new || opts.playlist_only_existing || opts.order != OrderArg::Ayah || opts.concat || opts.interleave || opts.window.is_set() {
+        anyhow::bail!("{} {}", "--select, --order, --append-new, --playlist-only-existing, --concat, --interleave, --offset and --limit work per surah, not with".red().bold(), what.red().bold());
+    }
+    Ok(())
+}
+
+// The shared tail of --juz and multi-surah --chapter
+fn write_combined(
+    target: &Path,
+    parts: &[(String, Option<String>, String)],
+    popts: &hifz::PlaylistOpts,
+    r: &lookup::Reciter,
+    dry_run: bool,
+    json: bool,
+) -> Result<()> {
+    if dry_run {
+        for (base, spec, _) in parts {
+            print_playlist_plan(base, spec.as_deref(), popts, json)?;
+        }
+        return Ok(());
+    }
+    let m3u = hifz::build_combined_playlist(target, parts, popts)?;
+    println!("{} {} {}", "📝".yellow(), "Playlist".bold(), m3u.to_string_lossy().bold().blue());
+    println!("   {} {}", label("Reciter:"), r.reciter_name.bold().magenta());
+    print_repeat_gap(popts);
+    Ok(())
+}
+
+// Several surahs (--chapter 93,94,95 or 78-114) walked in order into one
+// playlist; --verses applies to each of them
+async fn multi_playlist(
+    client: &Client,
+    fetch: &download::FilterOpts,
+    chapters: &[&lookup::Chapter],
+    verses: Option<&str>,
+    opts: &HifzOpts,
+    json: bool,
+) -> Result<()> {
+    check_combined(opts, "several chapters")?;
+    let popts = playlist_opts(opts)?;
+    let rec = opts.reciter.as_deref()
+        .ok_or_else(|| anyhow::anyhow!("{}", "--reciter is required with several chapters".yellow().bold()))?;
+    let reciters = lookup::fetch_reciters(client, &fetch.retry).await?;
+    let r = pick_reciter(&reciters, rec, &opts.pick)?;
+    let rslug = lookup::slugify(&r.reciter_name);
+
+    println!(
+        "{} {} {}",
+        "".bright_black(),
+        label("Surahs"),
+        format!("→ {}", chapters.iter().map(|c| c.name_simple.as_str()).collect::<Vec<_>>().join(", ")).dimmed()
+    );
+
+    let mut parts = Vec::new();
+    let mut total = 0usize;
+    let mut plays = 0usize;
+    for &c in chapters {
+        let count = c.verses_count;
+        let base = per_surah_base(&opts.out, &lookup::chapter_slug(c), &rslug);
+        // a shared --verses may ask past a shorter surah's end; keep what fits
+        let (spec, only) = match verses {
+            Some(v) => {
+                let asked = hifz::parse_verses_spec(v, count)?;
+                let fits: Vec<u32> = asked.iter().copied().filter(|&a| count.is_none_or(|n| a <= n)).collect();
+                if fits.is_empty() {
+                    println!("   {} {}", format!("{:03} {}", c.id, c.name_simple).bold(), "nothing in --verses".dimmed());
+                    continue;
+                }
+                let spec = if fits.len() == asked.len() { v.to_string() } else { hifz::format_verses_spec(&fits) };
+                (Some(spec), Some(fits))
+            }
+            None => (None, None),
+        };
+        // the whole surah, or what's on disk when the API left its ayah count out
+        let counted = match (&only, count) {
+            (Some(l), _) => l.clone(),
+            (None, Some(n)) => (1..=n).collect(),
+            (None, None) if Path::new(&base).is_dir() => hifz::detect_ayahs_shifted(Path::new(&base), popts.stem_shift)?,
+            (None, None) => Vec::new(),
+        };
+        total += counted.len();
+        plays += popts.plays(&counted);
+        if opts.print_spec {
+            let list = match &only {
+                Some(l) => l.clone(),
+                None if Path::new(&base).is_dir() => hifz::detect_ayahs_shifted(Path::new(&base), popts.stem_shift)?,
+                None => Vec::new(),
+            };
+            println!("{}:{}", c.id, hifz::format_verses_spec(&list));
+            continue;
+        }
+        println!("   {} {}", format!("{:03} {}", c.id, c.name_simple).bold(), spec.as_deref().unwrap_or("all").dimmed());
+        if !opts.dry_run && !opts.count {
+            let fo = download::FilterOpts { force: opts.force, only_verses: only, require_segments: opts.require_segments, jobs: opts.jobs as usize, tags: Some(tag_info(c, r)), normalize: opts.loudness.target()?, ..fetch.clone() };
+            download::run_filter(client, r.id, c.id, &base, &fo).await?;
+            if opts.write_info {
+                write_info_file(&opts.out, c, &r.reciter_name, opts.force);
+            }
+        }
+        parts.push((base, spec, c.name_simple.clone()));
+    }
+    if opts.print_spec {
+        return Ok(());
+    }
+    if opts.count {
+        println!(
+            "{} {} {}",
+            "#".cyan(),
+            label("Count:"),
+            plays_text(total, plays, &popts).bold()
+        );
+        return Ok(());
+    }
+
+    let ids: Vec<u32> = chapters.iter().map(|c| c.id).collect();
+    let contiguous = ids.windows(2).all(|w| w[1] == w[0] + 1);
+    let name = match (ids.first(), ids.last()) {
+        (Some(a), Some(b)) if contiguous => format!("surahs-{:03}-{:03}", a, b),
+        _ => format!("surahs-{}", ids.iter().map(|id| format!("{:03}", id)).collect::<Vec<_>>().join("_")),
+    };
+    let target = PathBuf::from(per_surah_base(&opts.out, &name, &rslug));
+    write_combined(&target, &parts, &popts, r, opts.dry_run, json)
+}
+
+// sum probed ayah lengths + gap lengths; unknown if any ayah can't be probed
+fn estimate_ms(entries: &[hifz::PlaylistEntry]) -> Option<u64> {
+    let mut durations = std::collections::HashMap::new();
+    let mut total_ms = Some(0u64);
+    for e in entries {
+        let ms = match e.kind {
+            hifz::EntryKind::Ayah => *durations.entry(e.path.clone())
+                .or_insert_with(|| probe::duration_ms(&e.path)),
+            hifz::EntryKind::Gap => e.gap_ms,
+            hifz::EntryKind::Missing => Some(0),
+        };
+        total_ms = total_ms.zip(ms).map(|(t, m)| t + m as u64);
+    }
+    total_ms
+}
+
+fn fmt_length(ms: Option<u64>) -> String {
+    match ms {
+        Some(ms) => format!("≈ {}:{:02}", ms / 60_000, ms / 1000 % 60).bold().to_string(),
+        None => "unknown (needs ffprobe)".dimmed().to_string(),
+    }
+}
+
+// --count for playlists: selection size and playlist length, nothing else
+fn print_playlist_count(out_base: &str, verses: Option<&str>, popts: &hifz::PlaylistOpts, json: bool) -> Result<()> {
+    let entries = hifz::plan_ayah_playlist(out_base, verses, popts, true)?;
+    // each ayah once, in play order (cumulative passes repeat them)
+    let mut seen = std::collections::HashSet::new();
+    let list: Vec<u32> = entries.iter()
+        .filter(|e| e.kind != hifz::EntryKind::Gap && seen.insert(e.ayah))
+        .map(|e| e.ayah)
+        .collect();
+    let ayahs = list.len();
+    let missing = entries.iter().filter(|e| e.kind == hifz::EntryKind::Missing).count();
+    let plays = popts.plays(&list);
+    let total_ms = if missing == 0 { estimate_ms(&entries) } else { None };
+    if json {
+        let report = serde_json::json!({
+            "ayahs": ayahs,
+            "repeat": popts.repeat,
+            "repeat_for": popts.repeat_for,
+            "cumulative": popts.cumulative,
+            "plays": plays,
+            "not_downloaded": missing,
+            "estimated_ms": total_ms,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!(
+        "{} {} {}",
+        "#".cyan(),
+        label("Count:"),
+        plays_text(ayahs, plays, popts).bold()
+    );
+    println!(
+        "   {} {}",
+        label("Length:"),
+        if missing > 0 {
+            format!("unknown ({} not downloaded yet)", missing).dimmed().to_string()
+        } else {
+            fmt_length(total_ms)
+        }
+    );
+    Ok(())
+}
+
+// --dry-run for playlists: list every entry, count them, estimate the length
+fn print_playlist_plan(out_base: &str, verses: Option<&str>, popts: &hifz::PlaylistOpts, json: bool) -> Result<()> {
+    let entries = hifz::plan_ayah_playlist(out_base, verses, popts, true)?;
+    let written = entries.iter().filter(|e| e.kind != hifz::EntryKind::Missing).count();
+    let total_ms = estimate_ms(&entries);
+
+    let m3u = hifz::playlist_path(&PathBuf::from(out_base), popts);
+    if json {
+        let report = serde_json::json!({
+            "playlist": m3u,
+            "entries": entries,
+            "total_entries": written,
+            "estimated_ms": total_ms,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("{} {} {} {}", "🔍".cyan(), "Dry run".bold(), m3u.display().bold().blue(), "(nothing written)".dimmed());
+    for e in &entries {
+        let state = if e.exists { "✔".green().to_string() } else { "✘".red().to_string() };
+        match e.kind {
+            hifz::EntryKind::Ayah => println!(
+                "   {} {} {}  {}",
+                format!("{:03}", e.ayah).bold().cyan(),
+                format!("×{}", e.take.unwrap_or(1)).dimmed(),
+                e.path.display(),
+                state
+            ),
+            hifz::EntryKind::Gap => println!(
+                "   {} {}  {}",
+                format!("gap {} ms", e.gap_ms.unwrap_or(0)).dimmed(),
+                e.path.display().dimmed(),
+                if e.exists { state } else { "(generated on build)".dimmed().to_string() }
+            ),
+            hifz::EntryKind::Missing => println!(
+                "   {} {}  {}",
+                format!("{:03}", e.ayah).bold().red(),
+                e.path.display(),
+                "missing, would be skipped".red()
+            ),
+        }
+    }
+    println!(
+        "   {} {}  {} {}",
+        label("Entries:"),
+        written.to_string().bold(),
+        label("Length:"),
+        fmt_length(total_ms)
+    );
+    Ok(())
+}
@@ -33,11 +33,31 @@ pub struct Verse {
     pub page_number: Option<u32>,
     pub juz_number: Option<u32>,
 
+    // missing/null when the reciter doesn't cover this ayah
+    #[serde(default, deserialize_with = "null_as_default")]
     pub audio: Audio,
+
+    // only there when asked for with ?translations=
+    #[serde(default)]
+    pub translations: Vec<VerseTranslation>,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct VerseTranslation {
+    #[serde(default)]
+    pub resource_id: u32,
+    // HTML, with <sup> footnote markers
+    #[serde(default)]
+    pub text: String,
+}
+
+fn null_as_default<'de, D: serde::Deserializer<'de>, T: Default + Deserialize<'de>>(de: D) -> Result<T, D::Error> {
+    Ok(Option::<T>::deserialize(de)?.unwrap_or_default())
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct Audio {
+    #[serde(default)]
     pub url: String,
 
     // Accept either [start,end] or [i,j,start,end]
@@ -45,7 +65,7 @@ pub struct Audio {
     pub segments: Option<Vec<Segment>>,
 }
 
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Debug)]
 pub struct Segment {
     pub i: Option<u32>,
     pub j: Option<u32>,
@@ -64,3 +84,14 @@ impl<'de> Deserialize<'de> for Segment {
         }
     }
 }
+
+// Written back in the API's array form, so segments.json (and any cached
+// verses) read in again the same way
+impl Serialize for Segment {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        match (self.i, self.j) {
+            (Some(i), Some(j)) => [i, j, self.start_ms, self.end_ms].serialize(ser),
+            _ => [self.start_ms, self.end_ms].serialize(ser),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::{Read, Seek, SeekFrom}, path::Path};
+
+/// What ffprobe (plus a peek at the first bytes) says about one audio file
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeInfo {
+    pub path: String,
+    pub bytes: u64,
+    pub codec: Option<String>,
+    pub duration_ms: Option<u32>,
+    pub bit_rate: Option<u32>,      // bits per second
+    pub sample_rate: Option<u32>,   // Hz
+    pub channels: Option<u32>,
+    pub looks_like_audio: bool,     // false usually means an HTML/JSON error page
+    pub gapless: Option<Gapless>,   // mp3s with a LAME info frame only
+}
+
+/// What gapless players trim from an mp3: the encoder's priming samples at
+/// the start and its padding at the end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Gapless {
+    pub delay: u32,
+    pub padding: u32,
+}
+
+// Sniff the container signature: ID3 tag / MPEG frame sync, Ogg, MP4, FLAC, WAV
+pub fn looks_like_audio(head: &[u8]) -> bool {
+    head.starts_with(b"ID3")
+        || (head.len() >= 2 && head[0] == 0xFF && head[1] & 0xE0 == 0xE0)
+        || head.starts_with(b"OggS")
+        || head.starts_with(b"fLaC")
+        || head.starts_with(b"RIFF")
+        || head.get(4..8) == Some(b"ftyp".as_slice())
+}
+
+// The "Xing"/"Info" frame that opens a LAME-style mp3 (`frame` starts at its
+// sync word) and the delay/padding in the LAME extension behind it. ffmpeg
+// writes its own name ("Lavc…") where LAME puts "LAME3.100".
+pub fn lame_gapless(frame: &[u8]) -> Option<Gapless> {
+    if frame.len() < 4 || frame[0] != 0xFF || frame[1] & 0xE0 != 0xE0 { return None; }
+    // the tag follows the side info, 9 to 32 bytes depending on version and channels
+    let at = 4 + frame.get(4..48)?.windows(4).position(|w| w == b"Xing" || w == b"Info")?;
+    let flags = u32::from_be_bytes(frame.get(at + 4..at + 8)?.try_into().ok()?);
+    // frame count, byte count, seek table, quality: each there if flagged
+    let off = at + 8 + [(1, 4), (2, 4), (4, 100), (8, 4)].iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, len)| len)
+        .sum::<usize>();
+    let ext = frame.get(off..off + 24)?;
+    if !ext[..4].iter().all(u8::is_ascii_alphanumeric) { return None; }
+    let d = &ext[21..24];
+    Some(Gapless {
+        delay: (u32::from(d[0]) << 4) | (u32::from(d[1]) >> 4),
+        padding: (u32::from(d[1] & 0x0F) << 8) | u32::from(d[2]),
+    })
+}
+
+// Past any ID3v2 tag (cover art can make it big) to the first frame
+fn read_gapless(path: &Path) -> Option<Gapless> {
+    let mut f = File::open(path).ok()?;
+    let mut id3 = [0u8; 10];
+    f.read_exact(&mut id3).ok()?;
+    let skip = if id3.starts_with(b"ID3") {
+        let size = id3[6..10].iter().fold(0u64, |n, &b| (n << 7) | u64::from(b & 0x7F));
+        10 + size + if id3[5] & 0x10 != 0 { 10 } else { 0 }
+    } else {
+        0
+    };
+    f.seek(SeekFrom::Start(skip)).ok()?;
+    let mut frame = Vec::with_capacity(256);
+    f.take(256).read_to_end(&mut frame).ok()?;
+    lame_gapless(&frame)
+}
+
+// ffprobe prints numbers as strings in its json output
+#[derive(Deserialize)]
+struct FfprobeOut {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    format: Option<FfprobeFormat>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+    codec_name: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+fn ffprobe(path: &Path) -> Option<FfprobeOut> {
+    let out = crate::ffmpeg::ffprobe()
+        .args([
+            "-v","error",
+            "-select_streams","a:0",
+            "-show_entries","format=duration,bit_rate:stream=codec_name,sample_rate,channels",
+            "-of","json",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !out.status.success() { return None; }
+    serde_json::from_slice(&out.stdout).ok()
+}
+
+pub fn probe(path: &Path) -> Result<ProbeInfo> {
+    if !path.is_file() {
+        anyhow::bail!("no such file: {}", path.display());
+    }
+    let bytes = std::fs::metadata(path)?.len();
+
+    let mut head = [0u8; 16];
+    let n = File::open(path)
+        .and_then(|mut f| f.read(&mut head))
+        .with_context(|| format!("read {}", path.display()))?;
+
+    let mut info = ProbeInfo {
+        path: path.display().to_string(),
+        bytes,
+        codec: None,
+        duration_ms: None,
+        bit_rate: None,
+        sample_rate: None,
+        channels: None,
+        looks_like_audio: looks_like_audio(&head[..n]),
+        gapless: read_gapless(path),
+    };
+
+    if let Some(out) = ffprobe(path) {
+        if let Some(s) = out.streams.into_iter().next() {
+            info.codec = s.codec_name;
+            info.sample_rate = s.sample_rate.and_then(|v| v.parse().ok());
+            info.channels = s.channels;
+        }
+        if let Some(f) = out.format {
+            info.duration_ms = f.duration
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|secs| (secs * 1000.0).round() as u32);
+            info.bit_rate = f.bit_rate.and_then(|v| v.parse().ok());
+        }
+    }
+    Ok(info)
+}
+
+// Just the duration; None when ffprobe is missing or the file is unreadable
+pub fn duration_ms(path: &Path) -> Option<u32> {
+    probe(path).ok().and_then(|i| i.duration_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an MPEG-1 stereo frame header, 32 bytes of side info, then the tag
+    fn info_frame(flags: u32, encoder: &[u8; 9], delay_padding: [u8; 3]) -> Vec<u8> {
+        let mut f = vec![0xFF, 0xFB, 0x90, 0x64];
+        f.extend([0; 32]);
+        f.extend(b"Info");
+        f.extend(flags.to_be_bytes());
+        f.extend(vec![0; [(1, 4), (2, 4), (4, 100), (8, 4)].iter().filter(|(b, _)| flags & b != 0).map(|(_, l)| l).sum()]);
+        f.extend(encoder);
+        f.extend([0; 12]);
+        f.extend(delay_padding);
+        f.extend([0; 32]);
+        f
+    }
+
+    #[test]
+    fn lame_frames_give_delay_and_padding() {
+        // 576 = 0x240 and 1234 = 0x4D2, packed as 12 bits each
+        let frame = info_frame(0xF, b"LAME3.100", [0x24, 0x04, 0xD2]);
+        assert_eq!(lame_gapless(&frame), Some(Gapless { delay: 576, padding: 1234 }));
+        // ffmpeg's own name, and a tag without the seek table
+        let frame = info_frame(0x3, b"Lavc61.19", [0x24, 0x04, 0xD2]);
+        assert_eq!(lame_gapless(&frame), Some(Gapless { delay: 576, padding: 1234 }));
+    }
+
+    #[test]
+    fn plain_frames_have_no_gapless_info() {
+        let mut frame = vec![0xFF, 0xFB, 0x90, 0x64];
+        frame.extend([0x55; 200]);
+        assert_eq!(lame_gapless(&frame), None);
+        assert_eq!(lame_gapless(b"ID3\x04\0\0\0\0\0\0"), None);
+    }
+
+    // best effort: only runs where a real ffmpeg can encode
+    #[test]
+    fn ffmpeg_mp3s_keep_gapless_info() {
+        if crate::ffmpeg::detect_ffmpeg().is_none() { return; }
+        let dir = crate::testutil::TempDir::new();
+        let mp3 = dir.path().join("tone.mp3");
+        let made = crate::ffmpeg::ffmpeg()
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "lavfi", "-i", "sine=frequency=440:duration=1"])
+            .args(crate::ffmpeg::MP3_GAPLESS)
+            .args(["-f", "mp3"])
+            .arg(&mp3)
+            .status()
+            .is_ok_and(|s| s.success());
+        if !made || std::fs::metadata(&mp3).map_or(true, |m| m.len() < 1000) { return; }
+        let g = read_gapless(&mp3).expect("no LAME info frame in ffmpeg's mp3");
+        assert!(g.delay > 0, "{g:?}");
+    }
+}
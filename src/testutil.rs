@@ -0,0 +1,153 @@
+//! Test helpers: a scratch folder and canned-response HTTP servers, so the
+//! unit tests need neither extra crates nor the network.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A fresh folder under the system temp dir, removed on drop
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static N: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let n = N.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("hifzr-test-{}-{}", std::process::id(), n));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Write `data` to `name` inside the folder
+    pub fn file(&self, name: &str, data: &[u8]) -> PathBuf {
+        let p = self.0.join(name);
+        std::fs::write(&p, data).unwrap();
+        p
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// What the server does with one connection
+pub enum Reply {
+    /// Send this status line, headers and body
+    Http { status: u16, headers: Vec<(&'static str, String)>, body: Vec<u8> },
+    /// Read the request and hang up without answering
+    Hangup,
+}
+
+impl Reply {
+    pub fn ok(content_type: &str, body: impl Into<Vec<u8>>) -> Reply {
+        Reply::Http { status: 200, headers: vec![("Content-Type", content_type.to_string())], body: body.into() }
+    }
+
+    pub fn json(body: &str) -> Reply {
+        Reply::ok("application/json", body)
+    }
+
+    pub fn status(status: u16) -> Reply {
+        Reply::Http { status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl ToString) -> Reply {
+        if let Reply::Http { headers, .. } = &mut self { headers.push((name, value.to_string())); }
+        self
+    }
+}
+
+/// A server on 127.0.0.1 answering one connection per reply, in order
+pub struct MockServer {
+    pub url: String,
+    /// Request lines seen so far, e.g. "HEAD /1.mp3 HTTP/1.1"
+    pub requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub fn start(replies: Vec<Reply>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            for reply in replies {
+                let Ok((mut conn, _)) = listener.accept() else { return };
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") && conn.read(&mut byte).is_ok_and(|n| n == 1) {
+                    head.push(byte[0]);
+                }
+                let head = String::from_utf8_lossy(&head);
+                seen.lock().unwrap().push(head.lines().next().unwrap_or("").to_string());
+                let Reply::Http { status, headers, body } = reply else { continue };
+                let mut out = format!("HTTP/1.1 {status} X\r\nConnection: close\r\n");
+                if !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("content-length")) {
+                    out += &format!("Content-Length: {}\r\n", body.len());
+                }
+                for (k, v) in headers {
+                    out += &format!("{k}: {v}\r\n");
+                }
+                out += "\r\n";
+                let _ = conn.write_all(out.as_bytes());
+                if !head.starts_with("HEAD") { let _ = conn.write_all(&body); }
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    pub fn hits(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+/// A server answering every connection on its own thread: 429 while more
+/// than `limit` requests are in flight, otherwise (after a short pause) a
+/// tiny mp3
+pub struct ThrottledServer {
+    pub url: String,
+    /// 429s sent so far
+    pub throttled: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl ThrottledServer {
+    pub fn start(limit: usize) -> ThrottledServer {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let throttled = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let counter = throttled.clone();
+        std::thread::spawn(move || {
+            for conn in listener.incoming() {
+                let Ok(mut conn) = conn else { return };
+                let (counter, in_flight) = (counter.clone(), in_flight.clone());
+                std::thread::spawn(move || {
+                    let mut head = Vec::new();
+                    let mut byte = [0u8; 1];
+                    while !head.ends_with(b"\r\n\r\n") && conn.read(&mut byte).is_ok_and(|n| n == 1) {
+                        head.push(byte[0]);
+                    }
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    let out: &[u8] = if now > limit {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        b"HTTP/1.1 429 X\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+                    } else {
+                        std::thread::sleep(std::time::Duration::from_millis(30));
+                        b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: audio/mpeg\r\nContent-Length: 9\r\n\r\nID3 audio"
+                    };
+                    let _ = conn.write_all(out);
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        ThrottledServer { url, throttled }
+    }
+}